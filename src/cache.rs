@@ -0,0 +1,396 @@
+//! Persistent on-disk cache for fetched vectors.
+//!
+//! `DiskCache` wraps another fetcher. The first fetch writes the loader's
+//! output to a cache file named after a user-supplied key; later fetches,
+//! including in other processes, read that file instead of calling the
+//! loader. The file starts with a header holding a format version, the
+//! user-supplied version, the element count and a checksum of the payload.
+//! A missing, corrupt or mismatched file falls back to the loader.
+//!
+//! Elements are encoded with the `Codec` trait, implemented for primitive
+//! types, `String`, `Vec`, `Option` and small tuples.
+
+use crate::Fetcher;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Identifies cache files written by this crate.
+const MAGIC: &[u8; 4] = b"DVEC";
+
+/// The version of the cache file layout.
+const FORMAT_VERSION: u32 = 1;
+
+/// The size of the header: magic, format version, user version, element
+/// count, payload length and checksum.
+const HEADER_LEN: usize = 4 + 4 + 8 + 8 + 8 + 8;
+
+/// A type that can be written to and read back from a cache file.
+pub trait Codec: Sized {
+    /// Appends the encoding of `self` to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes a value from the start of `input`, advancing it.
+    ///
+    /// # Returns
+    ///
+    /// The decoded value, or `None` if `input` is truncated or invalid.
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+/// Splits `len` bytes off the start of `input`.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Some(bytes)
+}
+
+macro_rules! impl_codec {
+    ($($primitive:ty),*) => {
+        $(
+            impl Codec for $primitive {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Option<Self> {
+                    let bytes = take(input, std::mem::size_of::<$primitive>())?;
+                    Some(<$primitive>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+/// Sizes are always encoded as `u64`, independently of the platform.
+impl Codec for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        usize::try_from(u64::decode(input)?).ok()
+    }
+}
+
+impl Codec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(u8::from(*self));
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        match u8::decode(input)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::decode(input)?;
+        String::from_utf8(take(input, len)?.to_vec()).ok()
+    }
+}
+
+impl<T> Codec for Vec<T>
+where
+    T: Codec,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::decode(input)?;
+        (0..len).map(|_| T::decode(input)).collect()
+    }
+}
+
+impl<T> Codec for Option<T>
+where
+    T: Codec,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.is_some().encode(out);
+        if let Some(value) = self {
+            value.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        match bool::decode(input)? {
+            true => T::decode(input).map(Some),
+            false => Some(None),
+        }
+    }
+}
+
+macro_rules! impl_codec_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: Codec),*> Codec for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn encode(&self, out: &mut Vec<u8>) {
+                let ($($name,)*) = self;
+                $($name.encode(out);)*
+            }
+
+            fn decode(input: &mut &[u8]) -> Option<Self> {
+                Some(($($name::decode(input)?,)*))
+            }
+        }
+    };
+}
+
+impl_codec_tuple!(A, B);
+impl_codec_tuple!(A, B, C);
+impl_codec_tuple!(A, B, C, D);
+
+/// Computes the 64-bit FNV-1a hash of `bytes`.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The header of a cache file.
+struct Header {
+    version: u64,
+    count: usize,
+    payload_len: usize,
+    checksum: u64,
+}
+
+impl Header {
+    /// Decodes a header, checking the magic bytes and the format version.
+    fn decode(mut input: &[u8]) -> Option<Header> {
+        if take(&mut input, MAGIC.len())? != MAGIC || u32::decode(&mut input)? != FORMAT_VERSION {
+            return None;
+        }
+        Some(Header {
+            version: u64::decode(&mut input)?,
+            count: usize::decode(&mut input)?,
+            payload_len: usize::decode(&mut input)?,
+            checksum: u64::decode(&mut input)?,
+        })
+    }
+}
+
+/// A fetcher caching the output of another fetcher in a file.
+pub struct DiskCache<F> {
+    fetcher: F,
+    path: PathBuf,
+    version: u64,
+}
+
+impl<F> DiskCache<F> {
+    /// Constructs a new `DiskCache` around `fetcher`.
+    ///
+    /// The cache file is stored in `dir`, under a name derived from `key`.
+    /// Bump `version` whenever the loader's output changes, to discard the
+    /// files written by previous versions.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetcher` - The loader called when the cache cannot be used.
+    /// * `dir` - The directory of the cache file.
+    /// * `key` - Identifies the cached vector.
+    /// * `version` - The version of the cached data.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `DiskCache`.
+    pub fn new(fetcher: F, dir: impl AsRef<Path>, key: &str, version: u64) -> DiskCache<F> {
+        let name: String = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let file_name = format!("{}-{:016x}.dvec", name, checksum(key.as_bytes()));
+        DiskCache {
+            fetcher,
+            path: dir.as_ref().join(file_name),
+            version,
+        }
+    }
+
+    /// Returns the path of the cache file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Deletes the cache file, so the next fetch calls the loader.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if the file was deleted or did not exist.
+    pub fn invalidate(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the cache file and checks its payload, without decoding it.
+    ///
+    /// # Returns
+    ///
+    /// The header and the bytes of the file, or `None` if the file is
+    /// missing, corrupt or was written for another version.
+    fn read_payload(&self) -> Option<(Header, Vec<u8>)> {
+        let bytes = fs::read(&self.path).ok()?;
+        let header = Header::decode(&bytes).filter(|header| header.version == self.version)?;
+        let payload = bytes.get(HEADER_LEN..)?;
+        if payload.len() != header.payload_len || checksum(payload) != header.checksum {
+            return None;
+        }
+        Some((header, bytes))
+    }
+
+    /// Reads and decodes the cache file.
+    ///
+    /// # Returns
+    ///
+    /// The cached elements, or `None` if the file is missing, corrupt or
+    /// was written for another version.
+    fn read<T>(&self) -> Option<Vec<T>>
+    where
+        T: Codec,
+    {
+        let (header, bytes) = self.read_payload()?;
+        let mut input = &bytes[HEADER_LEN..];
+        let items = (0..header.count)
+            .map(|_| T::decode(&mut input))
+            .collect::<Option<Vec<T>>>()?;
+        input.is_empty().then_some(items)
+    }
+
+    /// Writes `items` to the cache file, replacing it atomically.
+    fn write<T>(&self, items: &[T]) -> io::Result<()>
+    where
+        T: Codec,
+    {
+        let mut payload = Vec::new();
+        for item in items {
+            item.encode(&mut payload);
+        }
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(MAGIC);
+        FORMAT_VERSION.encode(&mut bytes);
+        self.version.encode(&mut bytes);
+        items.len().encode(&mut bytes);
+        payload.len().encode(&mut bytes);
+        checksum(&payload).encode(&mut bytes);
+        bytes.extend_from_slice(&payload);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = self
+            .path
+            .with_extension(format!("{}.partial", std::process::id()));
+        fs::write(&partial, bytes)?;
+        fs::rename(&partial, &self.path)
+    }
+}
+
+/// Reads the cache file, falling back to the wrapped fetcher.
+///
+/// Failing to write the cache file does not fail the fetch: the next
+/// fetch simply calls the loader again.
+impl<T, F> Fetcher<T> for DiskCache<F>
+where
+    T: Codec,
+    F: Fetcher<T>,
+{
+    fn fetch_all(&mut self) -> Vec<T> {
+        if let Some(items) = self.read() {
+            return items;
+        }
+        let items = self.fetcher.fetch_all();
+        let _ = self.write(&items);
+        items
+    }
+
+    /// The element count is read from the header of a cache file whose
+    /// payload length and checksum are valid, without decoding the elements.
+    fn size_hint(&mut self) -> Option<usize> {
+        match self.read_payload() {
+            Some((header, _)) => Some(header.count),
+            None => self.fetcher.size_hint(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.fetcher.name()
+    }
+}
+
+/// Unit tests for `DiskCache`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DeferredVec;
+    use std::cell::Cell;
+
+    #[test]
+    /// Tests that the cache file replaces the loader, and that corrupt or
+    /// outdated files fall back to it.
+    fn caches_across_instances() {
+        let dir =
+            std::env::temp_dir().join(format!("deferred_vector-cache-{}", std::process::id()));
+        let calls = Cell::new(0);
+        let loader = || {
+            calls.set(calls.get() + 1);
+            vec![(1u32, String::from("one")), (2, String::from("two"))]
+        };
+        let expected = loader();
+        calls.set(0);
+
+        let mut tst = DeferredVec::new(DiskCache::new(loader, &dir, "rows/v", 1));
+        assert_eq!(tst.as_slice(), &expected[..]);
+        assert_eq!(calls.get(), 1);
+
+        let mut tst = DeferredVec::new(DiskCache::new(loader, &dir, "rows/v", 1));
+        assert_eq!(tst.len(), 2);
+        assert!(tst.is_deferred());
+        assert_eq!(tst.as_slice(), &expected[..]);
+        assert_eq!(calls.get(), 1);
+
+        let mut cache = DiskCache::new(loader, &dir, "rows/v", 1);
+        let mut bytes = fs::read(cache.path()).unwrap();
+        *bytes.last_mut().unwrap() ^= 0xff;
+        fs::write(cache.path(), bytes).unwrap();
+        assert_eq!(Fetcher::<(u32, String)>::size_hint(&mut cache), None);
+        assert_eq!(DeferredVec::new(cache).as_slice(), &expected[..]);
+        assert_eq!(calls.get(), 2);
+
+        let mut outdated = DeferredVec::new(DiskCache::new(loader, &dir, "rows/v", 2));
+        assert_eq!(outdated.as_slice(), &expected[..]);
+        assert_eq!(calls.get(), 3);
+
+        let cache = DiskCache::new(loader, &dir, "rows/v", 2);
+        cache.invalidate().unwrap();
+        assert!(!cache.path().exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+//! Single-threaded deferred vectors with interior mutability.
+//!
+//! `CellDeferredVec` follows `std::cell::OnceCell` semantics: it is fetched
+//! through `&self`, so it can live inside immutable data structures and be
+//! shared through `Rc` clones. Unlike `SyncDeferredVec`, it is not `Sync`.
+
+use std::cell::{OnceCell, RefCell};
+use std::ops::Deref;
+
+/// A single-threaded, lazily initialized vector accessed through `&self`.
+///
+/// This struct holds a `OnceCell<Vec<T>>` to store the vector and a
+/// `RefCell` around the `fetch_function`, so that an `FnMut` can be called
+/// from `&self`.
+pub struct CellDeferredVec<T, F = fn() -> Vec<T>> {
+    vec: OnceCell<Vec<T>>,
+    fetch_function: RefCell<F>,
+}
+
+/// Implement methods for `CellDeferredVec`.
+impl<T, F> CellDeferredVec<T, F>
+where
+    F: FnMut() -> Vec<T>,
+{
+    /// Constructs a new instance of `CellDeferredVec`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetch_function` - A function or closure to initialize the vector.
+    ///
+    /// # Returns
+    ///
+    /// A new, deferred instance of `CellDeferredVec`.
+    pub fn new(fetch_function: F) -> CellDeferredVec<T, F> {
+        CellDeferredVec {
+            vec: OnceCell::new(),
+            fetch_function: RefCell::new(fetch_function),
+        }
+    }
+
+    /// Fetches and initializes the vector if it has not been fetched yet.
+    ///
+    /// It panics if the `fetch_function` tries to access this same vector.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the fetched vector.
+    fn fetch(&self) -> &Vec<T> {
+        self.vec
+            .get_or_init(|| (self.fetch_function.borrow_mut())())
+    }
+
+    /// Fetches the vector and returns a reference to it.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the fetched vector.
+    pub fn force(&self) -> &Vec<T> {
+        self.fetch()
+    }
+
+    /// Fetches the vector and borrows its elements as a slice.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the fetched elements.
+    pub fn as_slice(&self) -> &[T] {
+        self.fetch()
+    }
+
+    /// Returns the length of the fetched vector.
+    ///
+    /// # Returns
+    ///
+    /// The length of the fetched vector.
+    pub fn len(&self) -> usize {
+        self.fetch().len()
+    }
+
+    /// Checks if the fetched vector is empty.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the fetched vector has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks if the vector is initialized.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the vector has not been fetched yet and `false` otherwise.
+    pub fn is_deferred(&self) -> bool {
+        self.vec.get().is_none()
+    }
+}
+
+/// Methods returning owned copies of the vector.
+impl<T, F> CellDeferredVec<T, F>
+where
+    T: std::clone::Clone,
+    F: FnMut() -> Vec<T>,
+{
+    /// Fetches and returns a copy of the vector.
+    ///
+    /// # Returns
+    ///
+    /// The fetched vector.
+    pub fn get(&self) -> Vec<T> {
+        self.fetch().clone()
+    }
+}
+
+/// Dereferencing fetches the vector, like `std::cell::LazyCell`.
+impl<T, F> Deref for CellDeferredVec<T, F>
+where
+    F: FnMut() -> Vec<T>,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.fetch()
+    }
+}
+
+/// Builds a fetched `CellDeferredVec` from a vector.
+///
+/// Its `fetch_function` returns an empty vector and is never called.
+impl<T> From<Vec<T>> for CellDeferredVec<T> {
+    fn from(vec: Vec<T>) -> CellDeferredVec<T> {
+        let deferred_vec = CellDeferredVec::new(Vec::new as fn() -> Vec<T>);
+        let _ = deferred_vec.vec.set(vec);
+        deferred_vec
+    }
+}
+
+/// Unit tests for `CellDeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Catalog {
+        name: String,
+        items: CellDeferredVec<u32, Box<dyn FnMut() -> Vec<u32>>>,
+    }
+
+    #[test]
+    /// Tests fetching through `&self` from a struct field shared with `Rc`.
+    fn fetches_through_shared_reference() {
+        let fetches = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&fetches);
+        let catalog = Rc::new(Catalog {
+            name: String::from("catalog"),
+            items: CellDeferredVec::new(Box::new(move || {
+                counter.set(counter.get() + 1);
+                vec![1, 2, 3]
+            })),
+        });
+        let shared = Rc::clone(&catalog);
+        assert!(catalog.items.is_deferred());
+
+        let name = &catalog.name;
+        let items = catalog.items.as_slice();
+        assert_eq!(name, "catalog");
+        assert_eq!(items, &[1, 2, 3]);
+        assert_eq!(shared.items.len(), 3);
+        assert_eq!(shared.items.get(), vec![1, 2, 3]);
+        assert_eq!(fetches.get(), 1);
+    }
+}
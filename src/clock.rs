@@ -0,0 +1,67 @@
+//! Time sources for expiring deferred vectors.
+//!
+//! `DeferredVec::with_ttl` measures the age of the fetched vector with a
+//! `Clock`. `SystemClock` is used by default; `ManualClock` only moves when
+//! told to, so expiry can be tested without sleeping.
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+}
+
+/// The system monotonic clock, backed by `Instant::now`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only advances when `advance` is called.
+///
+/// Clones share the same time, so a test can keep one handle and give
+/// another to a `DeferredVec`.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Constructs a new `ManualClock` stopped at the current instant.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `ManualClock`.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves the clock, and all of its clones, forward.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - How far to move the clock.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,217 @@
+//! Lazy combinators deriving a deferred vector from others.
+//!
+//! `DeferredVec::map`, `filter`, `zip` and `chain` consume their upstream
+//! vectors and return a new `DeferredVec` backed by one of the fetchers of
+//! this module. Nothing is fetched until the derived vector is accessed: its
+//! fetch then forces the upstream vectors and moves their elements through
+//! the transformation, so a pipeline of combinators stays deferred end to end.
+//!
+//! The upstream elements are moved out, not cloned. Fetching the derived
+//! vector again, for example after `reset`, fetches the upstream again.
+
+use crate::{DeferredVec, Fetcher};
+
+/// Forces `upstream` and moves its elements out.
+fn drain<T, F>(upstream: &mut DeferredVec<T, F>) -> Vec<T>
+where
+    F: Fetcher<T>,
+{
+    upstream.fetch();
+    upstream.take().unwrap_or_default()
+}
+
+/// A fetcher applying a function to each element of a deferred vector.
+///
+/// Created by `DeferredVec::map`.
+pub struct Map<T, F, G> {
+    upstream: DeferredVec<T, F>,
+    f: G,
+}
+
+impl<T, F, G> Map<T, F, G> {
+    /// Constructs a new instance of `Map`.
+    pub(crate) fn new(upstream: DeferredVec<T, F>, f: G) -> Map<T, F, G> {
+        Map { upstream, f }
+    }
+}
+
+/// The length is the length of the upstream vector.
+impl<T, U, F, G> Fetcher<U> for Map<T, F, G>
+where
+    F: Fetcher<T>,
+    G: FnMut(T) -> U,
+{
+    fn fetch_all(&mut self) -> Vec<U> {
+        drain(&mut self.upstream)
+            .into_iter()
+            .map(&mut self.f)
+            .collect()
+    }
+
+    fn size_hint(&mut self) -> Option<usize> {
+        self.upstream.len_hint()
+    }
+}
+
+/// A fetcher keeping the elements of a deferred vector matching a predicate.
+///
+/// Created by `DeferredVec::filter`.
+pub struct Filter<T, F, P> {
+    upstream: DeferredVec<T, F>,
+    predicate: P,
+}
+
+impl<T, F, P> Filter<T, F, P> {
+    /// Constructs a new instance of `Filter`.
+    pub(crate) fn new(upstream: DeferredVec<T, F>, predicate: P) -> Filter<T, F, P> {
+        Filter {
+            upstream,
+            predicate,
+        }
+    }
+}
+
+/// The length is unknown until fetched.
+impl<T, F, P> Fetcher<T> for Filter<T, F, P>
+where
+    F: Fetcher<T>,
+    P: FnMut(&T) -> bool,
+{
+    fn fetch_all(&mut self) -> Vec<T> {
+        drain(&mut self.upstream)
+            .into_iter()
+            .filter(&mut self.predicate)
+            .collect()
+    }
+}
+
+/// A fetcher pairing the elements of two deferred vectors.
+///
+/// Created by `DeferredVec::zip`.
+pub struct Zip<T, F, U, G> {
+    a: DeferredVec<T, F>,
+    b: DeferredVec<U, G>,
+}
+
+impl<T, F, U, G> Zip<T, F, U, G> {
+    /// Constructs a new instance of `Zip`.
+    pub(crate) fn new(a: DeferredVec<T, F>, b: DeferredVec<U, G>) -> Zip<T, F, U, G> {
+        Zip { a, b }
+    }
+}
+
+/// The length is the shorter of the upstream lengths, like `Iterator::zip`.
+impl<T, F, U, G> Fetcher<(T, U)> for Zip<T, F, U, G>
+where
+    F: Fetcher<T>,
+    G: Fetcher<U>,
+{
+    fn fetch_all(&mut self) -> Vec<(T, U)> {
+        drain(&mut self.a)
+            .into_iter()
+            .zip(drain(&mut self.b))
+            .collect()
+    }
+
+    fn size_hint(&mut self) -> Option<usize> {
+        Some(self.a.len_hint()?.min(self.b.len_hint()?))
+    }
+}
+
+/// A fetcher appending the elements of a deferred vector to another.
+///
+/// Created by `DeferredVec::chain`.
+pub struct Chain<T, F, G> {
+    a: DeferredVec<T, F>,
+    b: DeferredVec<T, G>,
+}
+
+impl<T, F, G> Chain<T, F, G> {
+    /// Constructs a new instance of `Chain`.
+    pub(crate) fn new(a: DeferredVec<T, F>, b: DeferredVec<T, G>) -> Chain<T, F, G> {
+        Chain { a, b }
+    }
+}
+
+/// The length is the sum of the upstream lengths.
+impl<T, F, G> Fetcher<T> for Chain<T, F, G>
+where
+    F: Fetcher<T>,
+    G: Fetcher<T>,
+{
+    fn fetch_all(&mut self) -> Vec<T> {
+        let mut vec = drain(&mut self.a);
+        vec.append(&mut drain(&mut self.b));
+        vec
+    }
+
+    fn size_hint(&mut self) -> Option<usize> {
+        Some(self.a.len_hint()? + self.b.len_hint()?)
+    }
+}
+
+/// Unit tests for the combinators of `DeferredVec`.
+#[cfg(test)]
+mod tests {
+    use crate::fetcher::FromIter;
+    use crate::DeferredVec;
+    use std::cell::Cell;
+
+    #[test]
+    /// Tests that a pipeline stays deferred until the final vector is accessed.
+    fn pipelines_stay_deferred() {
+        let calls = Cell::new(0);
+        let records = DeferredVec::new(|| {
+            calls.set(calls.get() + 1);
+            vec!["7,ada", "3,bob", "12,eve"]
+        });
+
+        let mut ids = records
+            .map(|record| record.split(',').next().unwrap().parse::<u32>().unwrap())
+            .filter(|id| *id > 5);
+        assert!(ids.is_deferred());
+        assert_eq!(calls.get(), 0);
+
+        assert_eq!(ids.as_slice(), &[7, 12]);
+        assert_eq!(calls.get(), 1);
+        ids.reload();
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    /// Tests that elements are moved rather than cloned, and that reloading
+    /// a derived vector fetches the upstream again.
+    fn moves_and_reloads() {
+        struct Record(u32);
+
+        let calls = Cell::new(0);
+        let mut ids = DeferredVec::new(|| {
+            calls.set(calls.get() + 1);
+            vec![Record(1), Record(2)]
+        })
+        .map(|record| record.0);
+        assert_eq!(ids.as_slice(), &[1, 2]);
+        assert_eq!(ids.reload(), &vec![1, 2]);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    /// Tests `zip` and `chain`, and the lengths known without fetching.
+    fn zip_and_chain() {
+        let mut pairs = DeferredVec::new(FromIter::new(1..=3))
+            .zip(DeferredVec::new(|| vec!['a', 'b']).with_len_function(|| 2));
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs.is_deferred());
+        assert_eq!(pairs.as_slice(), &[(1, 'a'), (2, 'b')]);
+
+        let mut all = DeferredVec::new(FromIter::new(0..2)).chain(DeferredVec::from(vec![5]));
+        assert_eq!(all.len(), 3);
+        assert!(all.is_deferred());
+        assert_eq!(all.as_slice(), &[0, 1, 5]);
+
+        let mut doubled = DeferredVec::new(FromIter::new(0..4)).map(|x| x * 2);
+        assert_eq!(doubled.len(), 4);
+        assert!(doubled.is_deferred());
+        assert_eq!(doubled.as_slice(), &[0, 2, 4, 6]);
+    }
+}
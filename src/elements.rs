@@ -0,0 +1,174 @@
+//! Per-element deferred vectors.
+//!
+//! `DeferredElements` has a known length, and computes each element on first
+//! access with a `compute` function of its index. Computed elements are
+//! memoized, and a bitmap records which indices have been materialized.
+
+use std::cell::{Cell, OnceCell};
+use std::ops::Index;
+
+/// The number of indices tracked by each word of the bitmap.
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A vector whose elements are computed lazily, one at a time.
+///
+/// This struct holds one `OnceCell<T>` per element, a bitmap of the
+/// materialized indices, and a `compute` function returning the element
+/// at the given index.
+pub struct DeferredElements<T, F = fn(usize) -> T> {
+    elements: Vec<OnceCell<T>>,
+    materialized: Vec<Cell<u64>>,
+    compute: F,
+}
+
+/// Implement methods for `DeferredElements`.
+impl<T, F> DeferredElements<T, F>
+where
+    F: Fn(usize) -> T,
+{
+    /// Constructs a new instance of `DeferredElements`.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The number of elements.
+    /// * `compute` - A function returning the element at the given index.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `DeferredElements` with no element computed.
+    pub fn new(len: usize, compute: F) -> DeferredElements<T, F> {
+        DeferredElements {
+            elements: (0..len).map(|_| OnceCell::new()).collect(),
+            materialized: (0..len.div_ceil(WORD_BITS)).map(|_| Cell::new(0)).collect(),
+            compute,
+        }
+    }
+
+    /// Returns the element at `index`, computing it on first access.
+    ///
+    /// It panics if `compute` accesses the element it is computing.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the element.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the element, or `None` if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let element = self.elements.get(index)?;
+        Some(element.get_or_init(|| {
+            let value = (self.compute)(index);
+            let word = &self.materialized[index / WORD_BITS];
+            word.set(word.get() | 1 << (index % WORD_BITS));
+            value
+        }))
+    }
+
+    /// Iterates over all the elements, computing each one as it is reached.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over references to the elements.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len()).map(|index| &self[index])
+    }
+
+    /// Returns the number of elements, without computing any of them.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Checks if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns the number of elements computed so far.
+    pub fn materialized_count(&self) -> usize {
+        self.materialized
+            .iter()
+            .map(|word| word.get().count_ones() as usize)
+            .sum()
+    }
+
+    /// Checks if no element has been computed yet.
+    ///
+    /// # Returns
+    ///
+    /// `true` if every element is still deferred and `false` otherwise.
+    pub fn is_deferred(&self) -> bool {
+        self.materialized.iter().all(|word| word.get() == 0)
+    }
+
+    /// Checks if the element at `index` has not been computed yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the element.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the element is deferred, or `index` is out of bounds, and
+    /// `false` otherwise.
+    pub fn is_element_deferred(&self, index: usize) -> bool {
+        self.materialized
+            .get(index / WORD_BITS)
+            .is_none_or(|word| word.get() & 1 << (index % WORD_BITS) == 0)
+    }
+}
+
+/// Indexing computes the element, and panics if `index` is out of bounds.
+impl<T, F> Index<usize> for DeferredElements<T, F>
+where
+    F: Fn(usize) -> T,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(element) => element,
+            None => panic!(
+                "index out of bounds: the len is {} but the index is {}",
+                self.len(),
+                index
+            ),
+        }
+    }
+}
+
+/// Unit tests for `DeferredElements`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Tests that elements are computed once, on first access, and tracked individually.
+    fn computes_elements_on_demand() {
+        let computed = Cell::new(0);
+        let tst = DeferredElements::new(100, |index| {
+            computed.set(computed.get() + 1);
+            index * index
+        });
+        assert!(tst.is_deferred());
+        assert_eq!(tst.len(), 100);
+
+        assert_eq!(tst.get(70), Some(&4900));
+        assert_eq!(tst[70], 4900);
+        assert_eq!(tst[3], 9);
+        assert_eq!(tst.get(100), None);
+        assert_eq!(computed.get(), 2);
+
+        assert!(!tst.is_deferred());
+        assert!(!tst.is_element_deferred(70));
+        assert!(tst.is_element_deferred(69));
+        assert!(tst.is_element_deferred(100));
+        assert_eq!(tst.materialized_count(), 2);
+
+        assert_eq!(
+            tst.iter().take(5).copied().collect::<Vec<_>>(),
+            vec![0, 1, 4, 9, 16]
+        );
+        assert_eq!(tst.materialized_count(), 6);
+    }
+}
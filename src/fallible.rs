@@ -0,0 +1,155 @@
+//! Fallible deferred vectors.
+//!
+//! `TryDeferredVec` behaves like `DeferredVec`, but its `fetch_function`
+//! returns a `Result<Vec<T>, E>`. A failed fetch leaves the vector deferred,
+//! so the next access retries, and the error is kept for inspection.
+
+use crate::trace;
+
+/// A lazily initialized vector whose initialization may fail.
+///
+/// This struct holds an `Option<Vec<T>>` to store the vector, the error
+/// produced by the last failed fetch (if any), and a `fetch_function` of
+/// type `F` returning `Result<Vec<T>, E>`.
+pub struct TryDeferredVec<T, E, F = fn() -> Result<Vec<T>, E>> {
+    vec: Option<Vec<T>>,
+    last_error: Option<E>,
+    fetch_function: F,
+}
+
+/// Implement methods for `TryDeferredVec`.
+impl<T, E, F> TryDeferredVec<T, E, F>
+where
+    F: FnMut() -> Result<Vec<T>, E>,
+{
+    /// Constructs a new instance of `TryDeferredVec`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetch_function` - A fallible function or closure to initialize the vector.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `TryDeferredVec` with `vec` initialized as `None`.
+    pub fn new(fetch_function: F) -> TryDeferredVec<T, E, F> {
+        TryDeferredVec {
+            vec: None,
+            last_error: None,
+            fetch_function,
+        }
+    }
+
+    /// Fetches and initializes the `vec` if it's `None`.
+    ///
+    /// On failure the error is stored as the last error and `vec` stays
+    /// `None`. On success the last error is cleared.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the fetched vector, or a reference to the error.
+    fn try_fetch(&mut self) -> Result<&mut Vec<T>, &E> {
+        if self.vec.is_none() {
+            match (self.fetch_function)() {
+                Ok(vec) => {
+                    self.last_error = None;
+                    self.vec = Some(vec);
+                }
+                Err(error) => {
+                    trace::failure(std::any::type_name::<F>(), std::any::type_name::<T>());
+                    return Err(self.last_error.insert(error));
+                }
+            }
+        }
+        Ok(self.vec.as_mut().unwrap())
+    }
+
+    /// Fetches the vector and borrows its elements as a slice.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the fetched elements, or the error of the failed fetch.
+    pub fn try_as_slice(&mut self) -> Result<&[T], &E> {
+        self.try_fetch().map(|vec| vec.as_slice())
+    }
+
+    /// Fetches the vector and borrows its elements as a mutable slice.
+    ///
+    /// # Returns
+    ///
+    /// A mutable slice over the fetched elements, or the error of the failed fetch.
+    pub fn try_as_mut_slice(&mut self) -> Result<&mut [T], &E> {
+        self.try_fetch().map(|vec| vec.as_mut_slice())
+    }
+
+    /// Returns the length of the fetched vector.
+    ///
+    /// # Returns
+    ///
+    /// The length of the fetched vector, or the error of the failed fetch.
+    pub fn try_len(&mut self) -> Result<usize, &E> {
+        self.try_fetch().map(|vec| vec.len())
+    }
+
+    /// Checks if the vector is initialized.
+    ///
+    /// # Returns
+    ///
+    /// `true` if `vec` is `None` (not yet fetched, or the last fetch failed)
+    /// and `false` otherwise.
+    pub fn is_deferred(&self) -> bool {
+        self.vec.is_none()
+    }
+
+    /// Returns the error produced by the last failed fetch.
+    ///
+    /// # Returns
+    ///
+    /// `Some` with the error if the most recent fetch failed, `None` otherwise.
+    pub fn last_error(&self) -> Option<&E> {
+        self.last_error.as_ref()
+    }
+}
+
+/// Methods returning owned copies of the vector.
+impl<T, E, F> TryDeferredVec<T, E, F>
+where
+    T: std::clone::Clone,
+    F: FnMut() -> Result<Vec<T>, E>,
+{
+    /// Fetches and returns a copy of the vector.
+    ///
+    /// # Returns
+    ///
+    /// The fetched vector, or the error of the failed fetch.
+    pub fn try_get(&mut self) -> Result<Vec<T>, &E> {
+        self.try_fetch().map(|vec| vec.clone())
+    }
+}
+
+/// Unit tests for `TryDeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Tests that a failed fetch stays deferred, keeps the error and is retried.
+    fn retries_after_failure() {
+        let mut attempts = 0;
+        let mut tst = TryDeferredVec::new(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(String::from("not ready"))
+            } else {
+                Ok(vec![1, 2, 3])
+            }
+        });
+        assert_eq!(tst.try_len(), Err(&String::from("not ready")));
+        assert!(tst.is_deferred());
+        assert_eq!(tst.last_error(), Some(&String::from("not ready")));
+
+        assert_eq!(tst.try_as_slice(), Ok(&[1, 2, 3][..]));
+        assert!(!tst.is_deferred());
+        assert_eq!(tst.last_error(), None);
+        assert_eq!(tst.try_get(), Ok(vec![1, 2, 3]));
+    }
+}
@@ -0,0 +1,238 @@
+//! Fetch sources for deferred vectors.
+//!
+//! `DeferredVec` is generic over the `Fetcher` trait. Closures returning a
+//! `Vec<T>` implement it directly; this module also provides fetchers built
+//! from an iterator (`FromIter`), from a vector to clone (`FromVec`) and from
+//! the receiving end of a channel (`FromChannel`). Third-party sources can
+//! implement `Fetcher` to carry their own state and configuration.
+
+use std::ops::Range;
+use std::sync::mpsc::Receiver;
+
+/// A source able to produce the contents of a deferred vector.
+pub trait Fetcher<T> {
+    /// Fetches all the elements.
+    fn fetch_all(&mut self) -> Vec<T>;
+
+    /// Fetches the elements in `range`.
+    ///
+    /// The default implementation fetches everything and keeps the range;
+    /// sources with cheap random access should override it.
+    fn fetch_range(&mut self, range: Range<usize>) -> Vec<T> {
+        self.fetch_all()
+            .into_iter()
+            .skip(range.start)
+            .take(range.len())
+            .collect()
+    }
+
+    /// Returns the number of elements `fetch_all` would produce, if it is
+    /// known without fetching.
+    fn size_hint(&mut self) -> Option<usize> {
+        None
+    }
+
+    /// Returns a name describing the source, used in diagnostics.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// Functions and closures returning a vector are fetchers.
+impl<T, F> Fetcher<T> for F
+where
+    F: FnMut() -> Vec<T>,
+{
+    fn fetch_all(&mut self) -> Vec<T> {
+        self()
+    }
+}
+
+/// A fetcher collecting the remaining items of an iterator.
+///
+/// The iterator is consumed by the first fetch, so fetching again only
+/// yields the items produced since then.
+///
+/// Consumed items cannot be produced again: ranges must be fetched in
+/// increasing order, as `fetch_range` panics on a range starting before
+/// the items already consumed.
+pub struct FromIter<I> {
+    iter: I,
+    position: usize,
+}
+
+impl<I> FromIter<I>
+where
+    I: Iterator,
+{
+    /// Constructs a new `FromIter` fetcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - Anything that can be turned into the iterator to collect.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `FromIter`.
+    pub fn new(iter: impl IntoIterator<IntoIter = I>) -> FromIter<I> {
+        FromIter {
+            iter: iter.into_iter(),
+            position: 0,
+        }
+    }
+}
+
+impl<I> Fetcher<I::Item> for FromIter<I>
+where
+    I: Iterator,
+{
+    fn fetch_all(&mut self) -> Vec<I::Item> {
+        let items: Vec<I::Item> = self.iter.by_ref().collect();
+        self.position += items.len();
+        items
+    }
+
+    /// The range is counted from the first item of the iterator. It panics
+    /// if `range` starts before the items already consumed.
+    fn fetch_range(&mut self, range: Range<usize>) -> Vec<I::Item> {
+        assert!(
+            range.start >= self.position,
+            "FromIter cannot fetch {:?}: the first {} items are already consumed",
+            range,
+            self.position
+        );
+        let items: Vec<I::Item> = self
+            .iter
+            .by_ref()
+            .skip(range.start - self.position)
+            .take(range.len())
+            .collect();
+        self.position = range.start + items.len();
+        items
+    }
+
+    /// The hint is only given when the iterator reports an exact size.
+    fn size_hint(&mut self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        }
+    }
+}
+
+/// A fetcher returning clones of a vector.
+pub struct FromVec<T> {
+    vec: Vec<T>,
+}
+
+impl<T> FromVec<T>
+where
+    T: std::clone::Clone,
+{
+    /// Constructs a new `FromVec` fetcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `vec` - The vector cloned by every fetch.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `FromVec`.
+    pub fn new(vec: Vec<T>) -> FromVec<T> {
+        FromVec { vec }
+    }
+}
+
+impl<T> Fetcher<T> for FromVec<T>
+where
+    T: std::clone::Clone,
+{
+    fn fetch_all(&mut self) -> Vec<T> {
+        self.vec.clone()
+    }
+
+    fn fetch_range(&mut self, range: Range<usize>) -> Vec<T> {
+        self.vec[range].to_vec()
+    }
+
+    fn size_hint(&mut self) -> Option<usize> {
+        Some(self.vec.len())
+    }
+}
+
+/// A fetcher receiving the elements from a channel.
+///
+/// Fetching blocks until every sender has been dropped, and collects all
+/// the elements received.
+pub struct FromChannel<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> FromChannel<T> {
+    /// Constructs a new `FromChannel` fetcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `receiver` - The receiving end of the channel.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `FromChannel`.
+    pub fn new(receiver: Receiver<T>) -> FromChannel<T> {
+        FromChannel { receiver }
+    }
+}
+
+impl<T> Fetcher<T> for FromChannel<T> {
+    fn fetch_all(&mut self) -> Vec<T> {
+        self.receiver.iter().collect()
+    }
+}
+
+/// Unit tests for the built-in fetchers.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    /// Tests fetching all, ranges and size hints from the built-in fetchers.
+    fn builtin_fetchers() {
+        let mut from_iter = FromIter::new(0..5);
+        assert_eq!(from_iter.size_hint(), Some(5));
+        assert_eq!(from_iter.fetch_range(1..3), vec![1, 2]);
+        assert_eq!(from_iter.fetch_range(4..5), vec![4]);
+        assert_eq!(from_iter.fetch_all(), Vec::<i32>::new());
+
+        let mut from_iter = FromIter::new(0..5);
+        assert_eq!(from_iter.fetch_range(1..3), vec![1, 2]);
+        assert_eq!(from_iter.fetch_all(), vec![3, 4]);
+
+        let mut from_vec = FromVec::new(vec!['a', 'b', 'c']);
+        assert_eq!(from_vec.size_hint(), Some(3));
+        assert_eq!(from_vec.fetch_range(1..3), vec!['b', 'c']);
+        assert_eq!(from_vec.fetch_all(), vec!['a', 'b', 'c']);
+
+        let (sender, receiver) = mpsc::channel();
+        let producer = thread::spawn(move || (0..3).for_each(|i| sender.send(i).unwrap()));
+        let mut from_channel = FromChannel::new(receiver);
+        assert_eq!(from_channel.size_hint(), None);
+        assert_eq!(from_channel.fetch_all(), vec![0, 1, 2]);
+        producer.join().unwrap();
+
+        let mut closure = || vec![1, 2, 3];
+        assert_eq!(closure.fetch_range(2..10), vec![3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already consumed")]
+    /// Tests that `FromIter` refuses ranges it already consumed instead of
+    /// returning other items.
+    fn from_iter_rejects_consumed_ranges() {
+        let mut tst = crate::RangedDeferredVec::from_fetcher(10, FromIter::new(0..10));
+        assert_eq!(tst.slice(5..6), &[5]);
+        assert_eq!(tst.slice(7..8), &[7]);
+        tst.slice(2..3);
+    }
+}
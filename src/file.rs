@@ -0,0 +1,370 @@
+//! File-backed fetchers.
+//!
+//! The fetchers of this module read a file when the deferred vector is
+//! accessed: `Lines` yields one `String` per line, `ParsedLines` parses each
+//! line with `FromStr` and reports errors per line, and `Records` decodes
+//! fixed-size little-endian binary records.
+//!
+//! `Fetcher::fetch_all` cannot fail, so these fetchers panic on I/O errors.
+//! Their `try_fetch_all` methods return the error instead, and can back a
+//! `TryDeferredVec`.
+
+use crate::Fetcher;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Returns the size of the file at `path`, in bytes, from its metadata.
+fn file_len(path: &Path) -> Option<usize> {
+    let len = fs::metadata(path).ok()?.len();
+    usize::try_from(len).ok()
+}
+
+/// Reads the lines of the file at `path`, without their line endings.
+fn read_lines(path: &Path) -> io::Result<Vec<String>> {
+    BufReader::new(File::open(path)?).lines().collect()
+}
+
+/// Unwraps the result of a fetch, panicking with the path on I/O errors.
+fn expect_read<T>(result: io::Result<T>, path: &Path) -> T {
+    result.unwrap_or_else(|error| panic!("failed to read {}: {}", path.display(), error))
+}
+
+/// A fetcher reading one `String` per line of a file.
+pub struct Lines {
+    path: PathBuf,
+    line_width: Option<usize>,
+}
+
+impl Lines {
+    /// Constructs a new `Lines` fetcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to read.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Lines`.
+    pub fn new(path: impl AsRef<Path>) -> Lines {
+        Lines {
+            path: path.as_ref().to_path_buf(),
+            line_width: None,
+        }
+    }
+
+    /// Declares that every line is `width` bytes long, line ending included.
+    ///
+    /// The size hint is then the file size divided by `width`, so `len`
+    /// does not read the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The size of each line in bytes, line ending included.
+    ///
+    /// # Returns
+    ///
+    /// The `Lines` fetcher with the fixed line width.
+    pub fn with_line_width(mut self, width: usize) -> Lines {
+        self.line_width = Some(width);
+        self
+    }
+
+    /// Reads all the lines of the file.
+    ///
+    /// # Returns
+    ///
+    /// The lines, or the I/O error that prevented reading them.
+    pub fn try_fetch_all(&mut self) -> io::Result<Vec<String>> {
+        read_lines(&self.path)
+    }
+}
+
+impl Fetcher<String> for Lines {
+    fn fetch_all(&mut self) -> Vec<String> {
+        expect_read(self.try_fetch_all(), &self.path)
+    }
+
+    fn size_hint(&mut self) -> Option<usize> {
+        Some(file_len(&self.path)? / self.line_width.filter(|width| *width > 0)?)
+    }
+
+    fn name(&self) -> &str {
+        self.path.to_str().unwrap_or("lines")
+    }
+}
+
+/// The error of a line that could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineError<E> {
+    /// The number of the line, starting at 1.
+    pub line: usize,
+    /// The error returned by `FromStr`.
+    pub error: E,
+}
+
+impl<E> fmt::Display for LineError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl<E> Error for LineError<E>
+where
+    E: Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// A fetcher parsing each line of a file into a `T`.
+///
+/// Each element is the parsed value or the `LineError` of its line, so one
+/// bad line does not prevent loading the others. Surrounding whitespace is
+/// trimmed before parsing.
+pub struct ParsedLines<T> {
+    lines: Lines,
+    parsed: PhantomData<fn() -> T>,
+}
+
+impl<T> ParsedLines<T>
+where
+    T: FromStr,
+{
+    /// Constructs a new `ParsedLines` fetcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to read.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `ParsedLines`.
+    pub fn new(path: impl AsRef<Path>) -> ParsedLines<T> {
+        ParsedLines {
+            lines: Lines::new(path),
+            parsed: PhantomData,
+        }
+    }
+
+    /// Declares that every line is `width` bytes long, line ending included.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The size of each line in bytes, line ending included.
+    ///
+    /// # Returns
+    ///
+    /// The `ParsedLines` fetcher with the fixed line width.
+    pub fn with_line_width(mut self, width: usize) -> ParsedLines<T> {
+        self.lines = self.lines.with_line_width(width);
+        self
+    }
+
+    /// Reads and parses all the lines of the file.
+    ///
+    /// # Returns
+    ///
+    /// The parsed lines, or the I/O error that prevented reading them.
+    pub fn try_fetch_all(&mut self) -> io::Result<Vec<Result<T, LineError<T::Err>>>> {
+        let lines = self.lines.try_fetch_all()?;
+        Ok(lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                line.trim().parse().map_err(|error| LineError {
+                    line: index + 1,
+                    error,
+                })
+            })
+            .collect())
+    }
+}
+
+impl<T> Fetcher<Result<T, LineError<T::Err>>> for ParsedLines<T>
+where
+    T: FromStr,
+{
+    fn fetch_all(&mut self) -> Vec<Result<T, LineError<T::Err>>> {
+        expect_read(self.try_fetch_all(), &self.lines.path)
+    }
+
+    fn size_hint(&mut self) -> Option<usize> {
+        self.lines.size_hint()
+    }
+
+    fn name(&self) -> &str {
+        self.lines.name()
+    }
+}
+
+/// A plain type stored as a fixed-size little-endian binary record.
+///
+/// This is implemented for the primitive numeric types; implement it for
+/// `#[repr(C)]`-style structs by decoding each field in turn.
+pub trait Record: Sized {
+    /// The size of a record in bytes.
+    const SIZE: usize;
+
+    /// Decodes a record from exactly `SIZE` little-endian bytes.
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_record {
+    ($($primitive:ty),*) => {
+        $(
+            impl Record for $primitive {
+                const SIZE: usize = std::mem::size_of::<$primitive>();
+
+                fn from_le_bytes(bytes: &[u8]) -> Self {
+                    <$primitive>::from_le_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_record!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+/// A fetcher decoding a file of fixed-size binary records.
+///
+/// The size hint is the file size divided by the record size, and ranges
+/// are read directly from their offset in the file.
+pub struct Records<T> {
+    path: PathBuf,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<T> Records<T>
+where
+    T: Record,
+{
+    /// Constructs a new `Records` fetcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to read.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Records`.
+    pub fn new(path: impl AsRef<Path>) -> Records<T> {
+        Records {
+            path: path.as_ref().to_path_buf(),
+            record: PhantomData,
+        }
+    }
+
+    /// Reads and decodes all the records of the file.
+    ///
+    /// # Returns
+    ///
+    /// The records, or the I/O error that prevented reading them. A file
+    /// whose size is not a multiple of the record size is invalid data.
+    pub fn try_fetch_all(&mut self) -> io::Result<Vec<T>> {
+        let bytes = fs::read(&self.path)?;
+        if bytes.len() % T::SIZE != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file size is not a multiple of the record size",
+            ));
+        }
+        Ok(bytes.chunks_exact(T::SIZE).map(T::from_le_bytes).collect())
+    }
+
+    /// Reads and decodes the records in `range`.
+    ///
+    /// # Returns
+    ///
+    /// The records, or the I/O error that prevented reading them.
+    pub fn try_fetch_range(&mut self, range: Range<usize>) -> io::Result<Vec<T>> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start((range.start * T::SIZE) as u64))?;
+        let mut bytes = vec![0; range.len() * T::SIZE];
+        file.read_exact(&mut bytes)?;
+        Ok(bytes.chunks_exact(T::SIZE).map(T::from_le_bytes).collect())
+    }
+}
+
+impl<T> Fetcher<T> for Records<T>
+where
+    T: Record,
+{
+    fn fetch_all(&mut self) -> Vec<T> {
+        expect_read(self.try_fetch_all(), &self.path)
+    }
+
+    fn fetch_range(&mut self, range: Range<usize>) -> Vec<T> {
+        expect_read(self.try_fetch_range(range), &self.path)
+    }
+
+    fn size_hint(&mut self) -> Option<usize> {
+        Some(file_len(&self.path)? / T::SIZE)
+    }
+
+    fn name(&self) -> &str {
+        self.path.to_str().unwrap_or("records")
+    }
+}
+
+/// Unit tests for the file-backed fetchers.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeferredVec, RangedDeferredVec};
+
+    /// Writes `contents` to a file unique to this process and test.
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("deferred_vector-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    /// Tests reading lines and parsing them with per-line errors.
+    fn lines_and_parsed_lines() {
+        let path = temp_file("lines", b"10\n20\nxx\n40\n");
+        let mut lines = DeferredVec::new(Lines::new(&path).with_line_width(3));
+        assert_eq!(lines.len(), 4);
+        assert!(lines.is_deferred());
+        assert_eq!(lines.as_slice(), &["10", "20", "xx", "40"]);
+
+        let mut parsed = DeferredVec::new(ParsedLines::<u32>::new(&path));
+        let values = parsed.as_slice();
+        assert_eq!(values[1], Ok(20));
+        assert_eq!(values[2].as_ref().unwrap_err().line, 3);
+        assert_eq!(values[3], Ok(40));
+        fs::remove_file(path).unwrap();
+
+        let mut missing = Lines::new("/nonexistent/deferred_vector");
+        assert!(missing.try_fetch_all().is_err());
+        assert_eq!(missing.size_hint(), None);
+    }
+
+    #[test]
+    /// Tests decoding binary records, in full and by range, with a size hint.
+    fn binary_records() {
+        let bytes: Vec<u8> = (0..5u32)
+            .flat_map(|value| (value * 1000).to_le_bytes())
+            .collect();
+        let path = temp_file("records", &bytes);
+        let mut records = DeferredVec::new(Records::<u32>::new(&path));
+        assert_eq!(records.len(), 5);
+        assert!(records.is_deferred());
+        assert_eq!(records.as_slice(), &[0, 1000, 2000, 3000, 4000]);
+
+        let mut ranged = RangedDeferredVec::from_fetcher(5, Records::<u32>::new(&path));
+        assert_eq!(ranged.slice(3..5), &[3000, 4000]);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+//! Asynchronous deferred vectors.
+//!
+//! `AsyncDeferredVec` is initialized by a `fetch_function` returning a
+//! `Future`. All concurrent awaiters share a single in-flight fetch: whichever
+//! of them is polled drives the loader, and the others are woken once it
+//! makes progress. No particular async runtime is required.
+//!
+//! Dropping the task driving the loader, for example on a timeout, is safe:
+//! the loader future is kept, and the next awaiter polled drives it. A task
+//! finding the loader busy flags it, so the driver wakes every awaiter once
+//! it releases the loader and none of them misses a wake-up.
+//!
+//! If the `fetch_function` or its future panics, the panic reaches the task
+//! driving the loader, and every later access panics as well.
+
+use std::future::{poll_fn, Future};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError, TryLockError};
+use std::task::{ready, Context, Poll, Wake, Waker};
+
+/// The progress of the fetch of an `AsyncDeferredVec`.
+enum State<F, Fut> {
+    Deferred(F),
+    Fetching(Pin<Box<Fut>>),
+    Fetched,
+    Failed,
+}
+
+/// The wakers of every task awaiting the in-flight fetch.
+///
+/// It is itself used as the waker of the loader future, so any awaiter can
+/// resume driving the fetch when the loader makes progress.
+#[derive(Default)]
+struct Awaiters {
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Awaiters {
+    /// Registers the waker of a task waiting for the fetch.
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+}
+
+impl Wake for Awaiters {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let wakers = mem::take(&mut *self.wakers.lock().unwrap_or_else(PoisonError::into_inner));
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// A lazily initialized vector whose `fetch_function` is asynchronous.
+///
+/// This struct holds a `OnceLock<Vec<T>>` to store the vector, the state of
+/// the fetch (the `fetch_function` before the first access, then the
+/// in-flight future), the wakers of the tasks awaiting it, and whether a
+/// task found the state locked by the driver.
+pub struct AsyncDeferredVec<T, F, Fut> {
+    vec: OnceLock<Vec<T>>,
+    state: Mutex<State<F, Fut>>,
+    awaiters: Arc<Awaiters>,
+    contended: AtomicBool,
+}
+
+/// Implement methods for `AsyncDeferredVec`.
+impl<T, F, Fut> AsyncDeferredVec<T, F, Fut>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Vec<T>>,
+{
+    /// Constructs a new instance of `AsyncDeferredVec`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetch_function` - A function or closure returning a future that resolves to the vector.
+    ///
+    /// # Returns
+    ///
+    /// A new, deferred instance of `AsyncDeferredVec`.
+    pub fn new(fetch_function: F) -> AsyncDeferredVec<T, F, Fut> {
+        AsyncDeferredVec {
+            vec: OnceLock::new(),
+            state: Mutex::new(State::Deferred(fetch_function)),
+            awaiters: Arc::default(),
+            contended: AtomicBool::new(false),
+        }
+    }
+
+    /// Locks the state, unless another task is driving the loader.
+    ///
+    /// Contention is flagged before trying again, so the driver either
+    /// releases the lock before the second try or sees the flag after
+    /// releasing it.
+    ///
+    /// # Returns
+    ///
+    /// The locked state, or `None` if another task holds it.
+    fn try_lock_state(&self) -> Option<MutexGuard<'_, State<F, Fut>>> {
+        let try_lock = || match self.state.try_lock() {
+            Ok(state) => Some(state),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        };
+        try_lock().or_else(|| {
+            self.contended.store(true, Ordering::SeqCst);
+            try_lock()
+        })
+    }
+
+    /// Starts or resumes the loader, with the state locked.
+    ///
+    /// It panics if a previous fetch panicked.
+    ///
+    /// # Returns
+    ///
+    /// `Poll::Ready` once the vector is stored, or `Poll::Pending`.
+    fn drive(&self, state: &mut State<F, Fut>) -> Poll<()> {
+        if let State::Deferred(_) = *state {
+            if let State::Deferred(fetch_function) = mem::replace(state, State::Failed) {
+                *state = State::Fetching(Box::pin(fetch_function()));
+            }
+        }
+        match state {
+            State::Fetching(future) => {
+                let waker = Waker::from(Arc::clone(&self.awaiters));
+                let vec = ready!(future.as_mut().poll(&mut Context::from_waker(&waker)));
+                *state = State::Fetched;
+                let _ = self.vec.set(vec);
+                Poll::Ready(())
+            }
+            State::Deferred(_) | State::Fetched => Poll::Ready(()),
+            State::Failed => panic!("the fetch of this AsyncDeferredVec panicked"),
+        }
+    }
+
+    /// Polls the shared fetch, starting it on the first call.
+    ///
+    /// The waker of `cx` is registered before trying to drive the loader, so
+    /// a task that finds another one polling the loader is still woken when
+    /// the fetch completes. A panic of the loader marks the fetch as failed
+    /// and wakes the other awaiters before unwinding.
+    ///
+    /// # Returns
+    ///
+    /// `Poll::Ready` with a reference to the fetched vector, or `Poll::Pending`.
+    fn poll_fetch(&self, cx: &mut Context<'_>) -> Poll<&Vec<T>> {
+        if let Some(vec) = self.vec.get() {
+            return Poll::Ready(vec);
+        }
+        self.awaiters.register(cx.waker());
+        let Some(mut state) = self.try_lock_state() else {
+            return Poll::Pending;
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.drive(&mut state)));
+        if result.is_err() {
+            *state = State::Failed;
+        }
+        drop(state);
+        if self.contended.swap(false, Ordering::SeqCst) || !matches!(result, Ok(Poll::Pending)) {
+            self.awaiters.wake_by_ref();
+        }
+        match result {
+            Ok(Poll::Ready(())) => Poll::Ready(self.vec.get().unwrap()),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Fetches the vector and returns a reference to it.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the fetched vector.
+    pub async fn force(&self) -> &Vec<T> {
+        poll_fn(|cx| self.poll_fetch(cx)).await
+    }
+
+    /// Fetches the vector and borrows its elements as a slice.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the fetched elements.
+    pub async fn as_slice(&self) -> &[T] {
+        self.force().await
+    }
+
+    /// Returns the length of the fetched vector.
+    ///
+    /// # Returns
+    ///
+    /// The length of the fetched vector.
+    pub async fn len(&self) -> usize {
+        self.force().await.len()
+    }
+
+    /// Checks if the fetched vector is empty.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the fetched vector has no elements.
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// Checks if the vector is initialized.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the fetch has not completed yet, or has panicked, and
+    /// `false` otherwise.
+    pub fn is_deferred(&self) -> bool {
+        self.vec.get().is_none()
+    }
+}
+
+/// Methods returning owned copies of the vector.
+impl<T, F, Fut> AsyncDeferredVec<T, F, Fut>
+where
+    T: std::clone::Clone,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Vec<T>>,
+{
+    /// Fetches and returns a copy of the vector.
+    ///
+    /// # Returns
+    ///
+    /// The fetched vector.
+    pub async fn get(&self) -> Vec<T> {
+        self.force().await.clone()
+    }
+}
+
+/// Unit tests for `AsyncDeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread::{self, Thread};
+    use std::time::Duration;
+
+    /// Wakes a thread blocked in `block_on`.
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// A minimal executor running a future to completion on the current thread.
+    fn block_on<R>(future: impl Future<Output = R>) -> R {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    /// A future that stays pending until `open` is set.
+    async fn gate(open: Arc<AtomicBool>) {
+        poll_fn(|cx| {
+            if open.load(Ordering::SeqCst) {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    #[test]
+    /// Tests that a second awaiter takes over an in-flight fetch started by the first.
+    fn awaiters_share_in_flight_fetch() {
+        let open = Arc::new(AtomicBool::new(false));
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let (gate_open, counter) = (Arc::clone(&open), Arc::clone(&fetches));
+        let tst = AsyncDeferredVec::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                gate(gate_open).await;
+                vec![1, 2, 3]
+            }
+        });
+
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let mut first = Box::pin(tst.len());
+        let mut second = Box::pin(tst.get());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(tst.is_deferred());
+
+        open.store(true, Ordering::SeqCst);
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(vec![1, 2, 3]));
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(3));
+        assert!(!tst.is_deferred());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    /// Tests that awaiters on many threads run the loader exactly once.
+    fn fetches_once_across_threads() {
+        const THREADS: usize = 16;
+        let open = Arc::new(AtomicBool::new(false));
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let (gate_open, counter) = (Arc::clone(&open), Arc::clone(&fetches));
+        let tst = Arc::new(AsyncDeferredVec::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                gate(gate_open).await;
+                (0..100).collect::<Vec<u32>>()
+            }
+        }));
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let tst = Arc::clone(&tst);
+                thread::spawn(move || block_on(tst.len()))
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(20));
+        open.store(true, Ordering::SeqCst);
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 100);
+        }
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(block_on(tst.as_slice())[99], 99);
+    }
+
+    /// Counts the times it is woken.
+    #[derive(Default)]
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    /// Tests that an awaiter finding the loader busy is woken, and takes
+    /// over, when the driving awaiter is dropped.
+    fn survives_dropped_driver() {
+        let (entered, wait_entered) = std::sync::mpsc::channel();
+        let (proceed, wait_proceed) = std::sync::mpsc::channel();
+        let tst = AsyncDeferredVec::new(move || {
+            let mut polls = 0;
+            poll_fn(move |cx| {
+                polls += 1;
+                if polls > 1 {
+                    return Poll::Ready(vec![1, 2]);
+                }
+                cx.waker().wake_by_ref();
+                entered.send(()).unwrap();
+                wait_proceed.recv().unwrap();
+                Poll::Pending
+            })
+        });
+
+        let tst = &tst;
+        let (dropped, wait_dropped) = std::sync::mpsc::channel();
+        thread::scope(|scope| {
+            let follower = scope.spawn(move || {
+                wait_entered.recv().unwrap();
+                let woken = Arc::new(CountWakes::default());
+                let waker = Waker::from(Arc::clone(&woken));
+                let mut cx = Context::from_waker(&waker);
+                let mut second = Box::pin(tst.len());
+                assert!(second.as_mut().poll(&mut cx).is_pending());
+                proceed.send(()).unwrap();
+                wait_dropped.recv().unwrap();
+                assert!(woken.0.load(Ordering::SeqCst) > 0);
+                second.as_mut().poll(&mut cx)
+            });
+
+            let mut first = Box::pin(tst.len());
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(first.as_mut().poll(&mut cx).is_pending());
+            drop(first);
+            dropped.send(()).unwrap();
+            assert_eq!(follower.join().unwrap(), Poll::Ready(2));
+        });
+    }
+
+    #[test]
+    /// Tests that a panicking loader fails the fetch for every awaiter,
+    /// with an explicit message.
+    fn panicking_loader_fails_fetch() {
+        let failing_future =
+            AsyncDeferredVec::new(|| poll_fn(|_| -> Poll<Vec<u32>> { panic!("loader failed") }));
+        let failing_function =
+            AsyncDeferredVec::new(|| -> std::future::Ready<Vec<u32>> { panic!("no loader") });
+
+        let first = panic::catch_unwind(AssertUnwindSafe(|| block_on(failing_future.len())));
+        assert!(first.is_err());
+        let again = panic::catch_unwind(AssertUnwindSafe(|| block_on(failing_future.len())));
+        assert_eq!(
+            again.unwrap_err().downcast_ref::<&str>(),
+            Some(&"the fetch of this AsyncDeferredVec panicked")
+        );
+        assert!(failing_future.is_deferred());
+
+        let first = panic::catch_unwind(AssertUnwindSafe(|| block_on(failing_function.len())));
+        assert!(first.is_err());
+        let again = panic::catch_unwind(AssertUnwindSafe(|| block_on(failing_function.len())));
+        assert_eq!(
+            again.unwrap_err().downcast_ref::<&str>(),
+            Some(&"the fetch of this AsyncDeferredVec panicked")
+        );
+    }
+}
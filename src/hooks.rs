@@ -0,0 +1,53 @@
+//! Lifecycle hooks.
+//!
+//! `DeferredVec` runs registered callbacks before its `fetch_function`,
+//! after it with the fetched elements, and when fetched data is dropped by
+//! `reset`, `reload` or time-to-live expiry. Hooks of the same kind run in
+//! registration order. They only see the elements, never the `DeferredVec`
+//! itself, so they cannot trigger a reentrant fetch.
+
+/// A hook run before the `fetch_function`.
+pub(crate) type BeforeFetchHook = Box<dyn FnMut() + Send + Sync>;
+
+/// A hook run with the fetched or evicted elements.
+pub(crate) type ElementsHook<T> = Box<dyn FnMut(&[T]) + Send + Sync>;
+
+/// The hooks registered on a `DeferredVec`.
+pub(crate) struct Hooks<T> {
+    pub(crate) before_fetch: Vec<BeforeFetchHook>,
+    pub(crate) after_fetch: Vec<ElementsHook<T>>,
+    pub(crate) on_evict: Vec<ElementsHook<T>>,
+}
+
+impl<T> Hooks<T> {
+    /// Runs the hooks registered to run before a fetch.
+    pub(crate) fn before_fetch(&mut self) {
+        for hook in &mut self.before_fetch {
+            hook();
+        }
+    }
+
+    /// Runs the hooks registered to run after a fetch.
+    pub(crate) fn after_fetch(&mut self, vec: &[T]) {
+        for hook in &mut self.after_fetch {
+            hook(vec);
+        }
+    }
+
+    /// Runs the hooks registered to run when fetched data is dropped.
+    pub(crate) fn on_evict(&mut self, vec: &[T]) {
+        for hook in &mut self.on_evict {
+            hook(vec);
+        }
+    }
+}
+
+impl<T> Default for Hooks<T> {
+    fn default() -> Hooks<T> {
+        Hooks {
+            before_fetch: Vec::new(),
+            after_fetch: Vec::new(),
+            on_evict: Vec::new(),
+        }
+    }
+}
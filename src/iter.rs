@@ -0,0 +1,312 @@
+//! Iterators over deferred vectors.
+//!
+//! The iterators of `DeferredVec` are lazy: creating one does not fetch the
+//! vector, pulling the first element does. `Iter` and `IterMut` borrow the
+//! `DeferredVec`, `IntoIter` consumes it.
+//!
+//! Fetching needs `&mut DeferredVec`, so `&DeferredVec` does not implement
+//! `IntoIterator`; `iter` takes `&mut self` instead.
+
+use crate::{DeferredVec, Fetcher};
+use std::{slice, vec};
+
+/// An iterator source, fetched on first use.
+struct Lazy<D, I> {
+    deferred: Option<D>,
+    iter: Option<I>,
+}
+
+impl<D, I> Lazy<D, I> {
+    /// Constructs a new instance of `Lazy` holding the unfetched source.
+    fn new(deferred: D) -> Lazy<D, I> {
+        Lazy {
+            deferred: Some(deferred),
+            iter: None,
+        }
+    }
+
+    /// Returns the iterator, turning the source into it on first call.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetch` - Fetches the source and returns an iterator over it.
+    fn force(&mut self, fetch: impl FnOnce(D) -> I) -> &mut I {
+        let deferred = &mut self.deferred;
+        self.iter
+            .get_or_insert_with(|| fetch(deferred.take().unwrap()))
+    }
+}
+
+/// An iterator over references to the elements of a `DeferredVec`.
+///
+/// Created by `DeferredVec::iter`.
+pub struct Iter<'a, T, F> {
+    lazy: Lazy<&'a mut DeferredVec<T, F>, slice::Iter<'a, T>>,
+}
+
+impl<'a, T, F> Iter<'a, T, F> {
+    /// Constructs a new instance of `Iter`, without fetching.
+    pub(crate) fn new(deferred_vec: &'a mut DeferredVec<T, F>) -> Iter<'a, T, F> {
+        Iter {
+            lazy: Lazy::new(deferred_vec),
+        }
+    }
+}
+
+impl<'a, T, F> Iter<'a, T, F>
+where
+    F: Fetcher<T>,
+{
+    /// Fetches the vector on first call.
+    fn force(&mut self) -> &mut slice::Iter<'a, T> {
+        self.lazy
+            .force(|deferred_vec| deferred_vec.as_slice().iter())
+    }
+}
+
+impl<'a, T, F> Iterator for Iter<'a, T, F>
+where
+    F: Fetcher<T>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.force().next()
+    }
+
+    /// The bounds are unknown until the first element is pulled.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.lazy.iter {
+            Some(iter) => iter.size_hint(),
+            None => (0, None),
+        }
+    }
+}
+
+impl<'a, T, F> DoubleEndedIterator for Iter<'a, T, F>
+where
+    F: Fetcher<T>,
+{
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.force().next_back()
+    }
+}
+
+/// An iterator over mutable references to the elements of a `DeferredVec`.
+///
+/// Created by `DeferredVec::iter_mut`, or by iterating over a
+/// `&mut DeferredVec`.
+pub struct IterMut<'a, T, F> {
+    lazy: Lazy<&'a mut DeferredVec<T, F>, slice::IterMut<'a, T>>,
+}
+
+impl<'a, T, F> IterMut<'a, T, F> {
+    /// Constructs a new instance of `IterMut`, without fetching.
+    pub(crate) fn new(deferred_vec: &'a mut DeferredVec<T, F>) -> IterMut<'a, T, F> {
+        IterMut {
+            lazy: Lazy::new(deferred_vec),
+        }
+    }
+}
+
+impl<'a, T, F> IterMut<'a, T, F>
+where
+    F: Fetcher<T>,
+{
+    /// Fetches the vector on first call.
+    fn force(&mut self) -> &mut slice::IterMut<'a, T> {
+        self.lazy
+            .force(|deferred_vec| deferred_vec.as_mut_slice().iter_mut())
+    }
+}
+
+impl<'a, T, F> Iterator for IterMut<'a, T, F>
+where
+    F: Fetcher<T>,
+{
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.force().next()
+    }
+
+    /// The bounds are unknown until the first element is pulled.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.lazy.iter {
+            Some(iter) => iter.size_hint(),
+            None => (0, None),
+        }
+    }
+}
+
+impl<'a, T, F> DoubleEndedIterator for IterMut<'a, T, F>
+where
+    F: Fetcher<T>,
+{
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        self.force().next_back()
+    }
+}
+
+/// An iterator moving the elements out of a `DeferredVec`.
+///
+/// Created by iterating over a `DeferredVec` by value.
+pub struct IntoIter<T, F> {
+    lazy: Lazy<DeferredVec<T, F>, vec::IntoIter<T>>,
+}
+
+impl<T, F> IntoIter<T, F>
+where
+    F: Fetcher<T>,
+{
+    /// Fetches the vector on first call.
+    fn force(&mut self) -> &mut vec::IntoIter<T> {
+        self.lazy.force(|mut deferred_vec| {
+            deferred_vec.fetch();
+            deferred_vec.take().unwrap_or_default().into_iter()
+        })
+    }
+}
+
+impl<T, F> Iterator for IntoIter<T, F>
+where
+    F: Fetcher<T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.force().next()
+    }
+
+    /// The bounds are unknown until the first element is pulled.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.lazy.iter {
+            Some(iter) => iter.size_hint(),
+            None => (0, None),
+        }
+    }
+}
+
+impl<T, F> DoubleEndedIterator for IntoIter<T, F>
+where
+    F: Fetcher<T>,
+{
+    fn next_back(&mut self) -> Option<T> {
+        self.force().next_back()
+    }
+}
+
+impl<T, F> IntoIterator for DeferredVec<T, F>
+where
+    F: Fetcher<T>,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, F>;
+
+    fn into_iter(self) -> IntoIter<T, F> {
+        IntoIter {
+            lazy: Lazy::new(self),
+        }
+    }
+}
+
+impl<'a, T, F> IntoIterator for &'a mut DeferredVec<T, F>
+where
+    F: Fetcher<T>,
+{
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T, F>;
+
+    fn into_iter(self) -> IterMut<'a, T, F> {
+        IterMut::new(self)
+    }
+}
+
+/// Builds a fetched `DeferredVec`, like `From<Vec<T>>`.
+impl<T> FromIterator<T> for DeferredVec<T> {
+    fn from_iter<I>(iter: I) -> DeferredVec<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        DeferredVec::from(iter.into_iter().collect::<Vec<T>>())
+    }
+}
+
+/// Fetches the vector, then appends the items.
+impl<T, F> Extend<T> for DeferredVec<T, F>
+where
+    F: Fetcher<T>,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.fetch().extend(iter);
+    }
+}
+
+/// Unit tests for the iterators of `DeferredVec`.
+#[cfg(test)]
+mod tests {
+    use crate::DeferredVec;
+    use std::cell::Cell;
+
+    #[test]
+    /// Tests that the borrowing iterators fetch on the first `next`, not
+    /// when created.
+    fn borrowing_iterators_are_lazy() {
+        let calls = Cell::new(0);
+        let mut tst = DeferredVec::new(|| {
+            calls.set(calls.get() + 1);
+            vec![1, 2, 3]
+        });
+
+        let mut iter = tst.iter();
+        assert_eq!(iter.size_hint(), (0, None));
+        assert_eq!(calls.get(), 0);
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(iter.next_back(), Some(&3));
+
+        for x in &mut tst {
+            *x *= 10;
+        }
+        assert_eq!(tst.iter_mut().next_back(), Some(&mut 30));
+        assert_eq!(tst.as_slice(), &[10, 20, 30]);
+        assert_eq!(calls.get(), 1);
+
+        tst.reset();
+        assert_eq!(tst.iter_mut().size_hint(), (0, None));
+        assert!(tst.is_deferred());
+    }
+
+    #[test]
+    /// Tests that the owning iterator fetches on the first `next`.
+    fn owning_iterator_is_lazy() {
+        let calls = Cell::new(0);
+        let tst = DeferredVec::new(|| {
+            calls.set(calls.get() + 1);
+            vec![String::from("a"), String::from("b")]
+        });
+
+        let mut iter = tst.into_iter();
+        assert_eq!(calls.get(), 0);
+        assert_eq!(iter.next().as_deref(), Some("a"));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![String::from("b")]);
+    }
+
+    #[test]
+    /// Tests `FromIterator` and `Extend`.
+    fn collect_and_extend() {
+        let mut tst: DeferredVec<u32> = (1..=3).collect();
+        assert!(!tst.is_deferred());
+        tst.extend([4, 5]);
+        assert_eq!(tst.as_slice(), &[1, 2, 3, 4, 5]);
+
+        let mut deferred = DeferredVec::new(|| vec![1]);
+        deferred.extend(vec![2]);
+        assert_eq!(deferred.as_slice(), &[1, 2]);
+    }
+}
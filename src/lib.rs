@@ -12,10 +12,12 @@
 //! - **Laziness**: The vector is not initialized until it's explicitly accessed. This
 //!   lazy initialization is beneficial for performance in cases where the vector might not
 //!   be used immediately or at all.
-//! - **Flexibility**: Works with any type `T` that implements the `Clone` trait, allowing
-//!   for a wide range of applications.
+//! - **Flexibility**: Works with any type `T`, including move-only resources such as file
+//!   handles or `Box<dyn Trait>`. Only `get`, which returns an owned copy, requires `Clone`.
 //! - **Custom Initialization**: The vector is initialized using a user-provided function,
-//!   offering flexibility in how the vector's contents are determined.
+//!   offering flexibility in how the vector's contents are determined. Closures may capture
+//!   their environment (a file path, a configuration struct, a connection handle...), and
+//!   one-shot `FnOnce` loaders are supported through `DeferredVec::new_once`.
 //!
 //! ## Usage
 //!
@@ -29,12 +31,168 @@
 //! Basic usage:
 //!
 //! ```
+//! use deferred_vector::DeferredVec;
+//!
 //! let mut deferred_vector = DeferredVec::new(|| vec![1, 2, 3]);
 //! assert_eq!(deferred_vector.is_deferred(), true);
 //! let initialized_vector = deferred_vector.get();
 //! assert_eq!(deferred_vector.is_deferred(), false);
 //! ```
 //!
+//! Capturing the environment:
+//!
+//! ```
+//! use deferred_vector::DeferredVec;
+//!
+//! let base = 10;
+//! let mut deferred_vector = DeferredVec::new(move || vec![base, base + 1]);
+//! assert_eq!(deferred_vector.get(), vec![10, 11]);
+//! ```
+//!
+//! Hiding the load latency with a background prefetch:
+//!
+//! ```
+//! use deferred_vector::DeferredVec;
+//!
+//! let mut deferred_vector = DeferredVec::new(|| vec![1, 2, 3]);
+//! deferred_vector.prefetch(); // returns immediately
+//! assert_eq!(deferred_vector.is_loading(), true);
+//! assert_eq!(deferred_vector.as_slice(), &[1, 2, 3]); // waits for the load
+//! assert_eq!(deferred_vector.stats().fetch_count, 1);
+//! ```
+//!
+//! Catching panics of the loader:
+//!
+//! ```
+//! use deferred_vector::{DeferredVec, PanicPolicy};
+//!
+//! let mut deferred_vector = DeferredVec::new(|| -> Vec<u32> { panic!("offline") })
+//!     .with_panic_policy(PanicPolicy::Poison);
+//! assert!(deferred_vector.try_as_slice().is_err());
+//! assert_eq!(deferred_vector.is_poisoned(), true);
+//! deferred_vector.clear_poison();
+//! ```
+//!
+//! Fallible loaders:
+//!
+//! ```
+//! use deferred_vector::TryDeferredVec;
+//!
+//! let mut deferred_vector = TryDeferredVec::new(|| "1,2,3".split(',').map(str::parse).collect());
+//! assert_eq!(deferred_vector.try_as_slice(), Ok(&[1, 2, 3][..]));
+//! ```
+//!
+//! Sharing between threads:
+//!
+//! ```
+//! use deferred_vector::SyncDeferredVec;
+//! use std::sync::Arc;
+//!
+//! let deferred_vector = Arc::new(SyncDeferredVec::new(|| vec![1, 2, 3]));
+//! let shared = Arc::clone(&deferred_vector);
+//! std::thread::spawn(move || assert_eq!(shared.len(), 3)).join().unwrap();
+//! assert_eq!(deferred_vector.is_deferred(), false);
+//! ```
+//!
+//! Fetching through `&self`, single-threaded:
+//!
+//! ```
+//! use deferred_vector::CellDeferredVec;
+//! use std::rc::Rc;
+//!
+//! let deferred_vector = Rc::new(CellDeferredVec::new(|| vec![1, 2, 3]));
+//! let shared = Rc::clone(&deferred_vector);
+//! assert_eq!(shared.as_slice(), &[1, 2, 3]);
+//! assert_eq!(deferred_vector.is_deferred(), false);
+//! ```
+//!
+//! Asynchronous loaders, with any executor:
+//!
+//! ```
+//! use deferred_vector::AsyncDeferredVec;
+//!
+//! # async fn example() {
+//! let deferred_vector = AsyncDeferredVec::new(|| async { vec![1, 2, 3] });
+//! assert_eq!(deferred_vector.len().await, 3);
+//! # }
+//! ```
+//!
+//! Iterating without cloning, fetching on the first element:
+//!
+//! ```
+//! use deferred_vector::DeferredVec;
+//!
+//! let mut deferred_vector = DeferredVec::new(|| vec![1, 2, 3]);
+//! for x in &mut deferred_vector {
+//!     *x *= 2;
+//! }
+//! assert_eq!(deferred_vector.iter().sum::<i32>(), 12);
+//! ```
+//!
+//! Deriving vectors lazily:
+//!
+//! ```
+//! use deferred_vector::DeferredVec;
+//!
+//! let records = DeferredVec::new(|| vec!["1,ada", "2,bob"]);
+//! let mut ids = records.map(|record| record.split(',').next().unwrap().to_string());
+//! assert_eq!(ids.is_deferred(), true);
+//! assert_eq!(ids.as_slice(), &["1", "2"]);
+//! ```
+//!
+//! Consuming elements while they are produced:
+//!
+//! ```
+//! use deferred_vector::{StreamState, StreamingDeferredVec};
+//!
+//! let mut deferred_vector = StreamingDeferredVec::from_iter(16, 0..1000);
+//! assert_eq!(deferred_vector.get(2), Some(&2));
+//! assert!(matches!(deferred_vector.state(), StreamState::Partial(_)));
+//! assert_eq!(deferred_vector.len(), 1000);
+//! assert_eq!(deferred_vector.state(), StreamState::Complete);
+//! ```
+//!
+//! Loading only the pages that are accessed:
+//!
+//! ```
+//! use deferred_vector::PagedDeferredVec;
+//!
+//! let mut deferred_vector = PagedDeferredVec::new(1000, 100, |page| (page * 100..page * 100 + 100).collect());
+//! assert_eq!(deferred_vector.get(250), Some(&250));
+//! assert_eq!(deferred_vector.loaded_pages(), 1);
+//! ```
+//!
+//! Computing each element on first access:
+//!
+//! ```
+//! use deferred_vector::DeferredElements;
+//!
+//! let deferred_vector = DeferredElements::new(1000, |index| index * 2);
+//! assert_eq!(deferred_vector[21], 42);
+//! assert_eq!(deferred_vector.materialized_count(), 1);
+//! ```
+//!
+//! Loading arbitrary windows:
+//!
+//! ```
+//! use deferred_vector::RangedDeferredVec;
+//!
+//! let mut deferred_vector = RangedDeferredVec::new(1_000_000, |range| range.collect());
+//! assert_eq!(deferred_vector.slice(100..103), &[100, 101, 102]);
+//! assert_eq!(deferred_vector.loaded_ranges(), vec![100..103]);
+//! ```
+//!
+//! ## Cargo features
+//!
+//! - `serde`: implements `Serialize` and `Deserialize` for `DeferredVec`,
+//!   `SyncDeferredVec` and `CellDeferredVec`. See the `serde` module.
+//! - `tracing`: runs each fetch of a `DeferredVec` in a `deferred_vec.fetch`
+//!   span recording its name (see `DeferredVec::with_name`), element type,
+//!   element count and duration, and emits events for cache hits, resets and
+//!   failed fetches of `TryDeferredVec`.
+//! - `log`: enables `tracing` and forwards its spans and events to the `log`
+//!   crate when no `tracing` subscriber is installed.
+//!
 //! ## Testing
 //!
 //! The module includes unit tests to verify the functionality, especially focusing on
@@ -53,92 +211,838 @@
 //!
 //! This project is licensed under the MIT License - see the LICENSE file for details.
 
+pub mod cache;
+mod cell;
+pub mod clock;
+pub mod combinator;
+mod elements;
+mod fallible;
+pub mod fetcher;
+pub mod file;
+mod future;
+mod hooks;
+pub mod iter;
+mod paged;
+mod poison;
+mod prefetch;
+mod ranged;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod stats;
+mod streaming;
+mod sync;
+mod trace;
+
+pub use cell::CellDeferredVec;
+pub use elements::DeferredElements;
+pub use fallible::TryDeferredVec;
+pub use fetcher::Fetcher;
+pub use future::AsyncDeferredVec;
+pub use paged::PagedDeferredVec;
+pub use poison::{PanicPolicy, Poisoned};
+pub use ranged::RangedDeferredVec;
+pub use stats::FetchStats;
+pub use streaming::{Sink, StreamState, StreamingDeferredVec};
+pub use sync::SyncDeferredVec;
+
+use clock::{Clock, SystemClock};
+use hooks::Hooks;
+use prefetch::Prefetch;
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+use trace::FetchSpan;
 
 /// A generic struct `DeferredVec` for lazily initializing a vector.
 ///
 /// This struct holds an `Option<Vec<T>>` to store the vector,
 /// which may or may not be present initially, and a `fetch_function`
-/// of type `fn() -> Vec<T>`, which is a function pointer
-/// that returns a vector of the same type when called.
-pub struct DeferredVec<T> {
+/// of type `F`, which returns a vector of the same type when called.
+///
+/// `F` defaults to the function pointer `fn() -> Vec<T>`, but any
+/// `Fetcher<T>` is accepted: capturing `FnMut() -> Vec<T>` closures, the
+/// built-in fetchers of the `fetcher` module, or third-party sources.
+///
+/// An optional time-to-live makes the fetched vector expire: the `clock`
+/// records when it was fetched, and access after the `ttl` fetches again.
+///
+/// Every fetch is recorded in `FetchStats`, readable through `stats`, and
+/// runs the lifecycle hooks registered with the `with_*_hook` methods.
+///
+/// An optional `len_function` reports the length cheaply, so `len` can be
+/// answered while the vector is still deferred. Otherwise, the `size_hint`
+/// of the fetcher is used.
+///
+/// An optional `name` identifies the vector in the diagnostics emitted
+/// with the `tracing` feature.
+///
+/// `prefetch` moves the `fetch_function` to a worker thread until the next
+/// access collects the result, so `fetch_function` is `None` only while
+/// `prefetch` is `Some`.
+///
+/// The `panic_policy` decides whether a panic of the `fetch_function`
+/// unwinds, or is caught and recorded in `poison`.
+pub struct DeferredVec<T, F = fn() -> Vec<T>> {
     vec: Option<Vec<T>>,
-    fetch_function: fn() -> Vec<T>,
+    fetch_function: Option<F>,
+    prefetch: Option<Prefetch<T, F>>,
+    name: Option<String>,
+    len_function: Option<Box<dyn FnMut() -> usize + Send + Sync>>,
+    ttl: Option<Duration>,
+    clock: Box<dyn Clock>,
+    fetched_at: Option<Instant>,
+    stats: FetchStats,
+    hooks: Hooks<T>,
+    panic_policy: PanicPolicy,
+    poison: Option<Poisoned>,
 }
 
 /// Implement methods for `DeferredVec`.
 ///
-/// The `#[allow(dead_code)]` attribute indicates
-/// that even if some methods are not used, they should not be considered dead code.
-/// The generic type `T` is bound by the trait `std::clone::Clone` to ensure
-/// that elements of the vector can be cloned.
-impl<T> DeferredVec<T>
+/// The generic type `F` is the fetch source, which may be called again
+/// to produce the vector. No bound is placed on `T`.
+impl<T, F> DeferredVec<T, F>
 where
-    T: std::clone::Clone,
+    F: Fetcher<T>,
 {
     /// Constructs a new instance of `DeferredVec`.
     ///
     /// # Arguments
     ///
-    /// * `fetch_function` - A function to initialize the vector.
+    /// * `fetch_function` - A function, closure or other `Fetcher` to initialize the vector.
     ///
     /// # Returns
     ///
     /// A new instance of `DeferredVec` with `vec` initialized as `None`.
-    pub fn new(fetch_function: fn() -> Vec<T>) -> DeferredVec<T> {
+    pub fn new(fetch_function: F) -> DeferredVec<T, F> {
         DeferredVec {
             vec: None,
-            fetch_function,
+            fetch_function: Some(fetch_function),
+            prefetch: None,
+            name: None,
+            len_function: None,
+            ttl: None,
+            clock: Box::new(SystemClock),
+            fetched_at: None,
+            stats: FetchStats::default(),
+            hooks: Hooks::default(),
+            panic_policy: PanicPolicy::default(),
+            poison: None,
+        }
+    }
+
+    /// Sets the name identifying the vector in diagnostics.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name, replacing the `name` of the fetcher.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` with the name set.
+    pub fn with_name(mut self, name: impl Into<String>) -> DeferredVec<T, F> {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets a function reporting the length without fetching the vector.
+    ///
+    /// Useful when the source knows its size cheaply, from file metadata, a
+    /// `COUNT` query or a header.
+    ///
+    /// # Arguments
+    ///
+    /// * `len_function` - A function returning the length the fetched vector will have.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` using `len_function` while deferred.
+    pub fn with_len_function(
+        mut self,
+        len_function: impl FnMut() -> usize + Send + Sync + 'static,
+    ) -> DeferredVec<T, F> {
+        self.len_function = Some(Box::new(len_function));
+        self
+    }
+
+    /// Registers a hook run before each call of the `fetch_function`.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The callback, run after the hooks registered before it.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` with the hook registered.
+    pub fn with_before_fetch_hook(
+        mut self,
+        hook: impl FnMut() + Send + Sync + 'static,
+    ) -> DeferredVec<T, F> {
+        self.hooks.before_fetch.push(Box::new(hook));
+        self
+    }
+
+    /// Registers a hook run with the elements produced by each fetch.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The callback, run after the hooks registered before it.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` with the hook registered.
+    pub fn with_after_fetch_hook(
+        mut self,
+        hook: impl FnMut(&[T]) + Send + Sync + 'static,
+    ) -> DeferredVec<T, F> {
+        self.hooks.after_fetch.push(Box::new(hook));
+        self
+    }
+
+    /// Registers a hook run with the fetched elements when they are dropped
+    /// by `reset`, `reload` or time-to-live expiry.
+    ///
+    /// Moving the elements out with `take` or `replace` does not run it.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The callback, run after the hooks registered before it.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` with the hook registered.
+    pub fn with_evict_hook(
+        mut self,
+        hook: impl FnMut(&[T]) + Send + Sync + 'static,
+    ) -> DeferredVec<T, F> {
+        self.hooks.on_evict.push(Box::new(hook));
+        self
+    }
+
+    /// Sets a time-to-live for the fetched vector.
+    ///
+    /// Once the fetched vector is older than `ttl`, the next access fetches
+    /// it again. A `ttl` too long to represent, such as `Duration::MAX`,
+    /// never expires.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - How long a fetched vector stays fresh.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` with the time-to-live set.
+    pub fn with_ttl(mut self, ttl: Duration) -> DeferredVec<T, F> {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the clock used to measure the age of the fetched vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The time source, `SystemClock` by default.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` using `clock`.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> DeferredVec<T, F> {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets what happens when the `fetch_function` panics.
+    ///
+    /// # Arguments
+    ///
+    /// * `panic_policy` - The policy, `PanicPolicy::Propagate` by default.
+    ///
+    /// # Returns
+    ///
+    /// The `DeferredVec` using `panic_policy`.
+    pub fn with_panic_policy(mut self, panic_policy: PanicPolicy) -> DeferredVec<T, F> {
+        self.panic_policy = panic_policy;
+        self
+    }
+
+    /// Fetches and initializes the `vec` if it's `None` or stale.
+    ///
+    /// A prefetch in flight is waited for instead of fetching again.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the fetched vector, or the poison recorded
+    /// by a caught panic of the `fetch_function`.
+    fn try_fetch(&mut self) -> Result<&mut Vec<T>, &Poisoned> {
+        if self.poison.is_none() {
+            if self.is_stale() {
+                self.reset();
+            }
+            if self.prefetch.is_some() {
+                self.collect_prefetch();
+            } else if self.vec.is_none() {
+                self.load(self.panic_policy.retries());
+            } else {
+                trace::cache_hit(self.name());
+                self.stats.cache_hits += 1;
+            }
+        }
+        match &self.poison {
+            Some(poisoned) => Err(poisoned),
+            None => Ok(self.vec.as_mut().unwrap()),
         }
     }
 
-    /// Fetches and initializes the `vec` if it's `None`.
+    /// Fetches and initializes the `vec` if it's `None` or stale.
     ///
-    /// Returns a cloned instance of the vector.
+    /// It panics if the vector is poisoned.
     ///
     /// # Returns
     ///
-    /// An `Option<Vec<T>>` which is the cloned instance of the vector.
-    fn fetch(&mut self) -> Option<Vec<T>> {
-        if self.vec.is_none() {
-            self.vec = Some((self.fetch_function)());
+    /// A mutable reference to the fetched vector.
+    fn fetch(&mut self) -> &mut Vec<T> {
+        match self.try_fetch() {
+            Ok(vec) => vec,
+            Err(poisoned) => panic!("{}", poisoned),
         }
-        self.vec.clone()
     }
 
-    /// Fetches and returns the vector.
+    /// Calls the `fetch_function` and installs its result.
+    ///
+    /// Unless the policy is `PanicPolicy::Propagate`, panics are caught:
+    /// the fetch is retried, then the vector is poisoned.
     ///
-    /// This method calls `fetch` and unwraps the result to get the vector.
-    /// It panics if `fetch` returns `None`.
+    /// # Arguments
+    ///
+    /// * `retries` - How many times a panicking fetch is retried.
+    fn load(&mut self, mut retries: u32) {
+        let span = FetchSpan::enter(self.name(), std::any::type_name::<T>());
+        self.hooks.before_fetch();
+        let started = self.clock.now();
+        let vec = loop {
+            if self.panic_policy == PanicPolicy::Propagate {
+                break self.fetcher().fetch_all();
+            }
+            let fetcher = self.fetcher();
+            match std::panic::catch_unwind(AssertUnwindSafe(|| fetcher.fetch_all())) {
+                Ok(vec) => break vec,
+                Err(_) if retries > 0 => retries -= 1,
+                Err(payload) => {
+                    trace::failure(self.name(), std::any::type_name::<T>());
+                    self.poison = Some(Poisoned::new(payload));
+                    return;
+                }
+            }
+        };
+        let finished = self.clock.now();
+        let duration = finished.saturating_duration_since(started);
+        span.record(vec.len(), duration);
+        self.install(vec, duration, finished);
+    }
+
+    /// Returns the fetcher, waiting for a prefetch in flight to hand it back.
+    fn fetcher(&mut self) -> &mut F {
+        self.collect_prefetch();
+        self.fetch_function.as_mut().unwrap()
+    }
+
+    /// Waits for a prefetch in flight, if any, and installs its result.
+    ///
+    /// A panic of the fetcher on the worker thread is handled here, once
+    /// the fetcher is back in place: it is resumed, retried on the calling
+    /// thread or recorded as poison, depending on the panic policy.
+    fn collect_prefetch(&mut self) {
+        if let Some(prefetch) = self.prefetch.take() {
+            let prefetched = prefetch.wait();
+            self.fetch_function = Some(prefetched.fetch_function);
+            match prefetched.result {
+                Ok(vec) => {
+                    let span = FetchSpan::enter(self.name(), std::any::type_name::<T>());
+                    span.record(vec.len(), prefetched.duration);
+                    self.install(vec, prefetched.duration, self.clock.now());
+                }
+                Err(payload) => match self.panic_policy {
+                    PanicPolicy::Propagate => {
+                        let _span = FetchSpan::enter(self.name(), std::any::type_name::<T>());
+                        std::panic::resume_unwind(payload)
+                    }
+                    PanicPolicy::Retry(retries) if retries > 0 => self.load(retries - 1),
+                    PanicPolicy::Retry(_) | PanicPolicy::Poison => {
+                        trace::failure(self.name(), std::any::type_name::<T>());
+                        self.poison = Some(Poisoned::new(payload));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Installs a freshly fetched vector and records the fetch.
+    ///
+    /// # Arguments
+    ///
+    /// * `vec` - The fetched vector.
+    /// * `duration` - The time spent in the `fetch_function`.
+    /// * `fetched_at` - The time of the fetch, for the time-to-live.
+    fn install(&mut self, vec: Vec<T>, duration: Duration, fetched_at: Instant) {
+        self.hooks.after_fetch(&vec);
+        self.stats.record_fetch(duration, vec.len());
+        self.vec = Some(vec);
+        self.fetched_at = Some(fetched_at);
+    }
+
+    /// Fetches the vector and returns a reference to it.
+    ///
+    /// `DeferredVec` does not implement `Deref`, since dereferencing cannot
+    /// trigger the fetch through `&self`; `force` is the explicit equivalent.
     ///
     /// # Returns
     ///
-    /// The fetched vector.
-    pub fn get(&mut self) -> Vec<T> {
-        self.fetch().unwrap().clone()
+    /// A reference to the fetched vector.
+    pub fn force(&mut self) -> &Vec<T> {
+        self.fetch()
+    }
+
+    /// Fetches the vector and returns a mutable reference to it.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the fetched vector.
+    pub fn force_mut(&mut self) -> &mut Vec<T> {
+        self.fetch()
+    }
+
+    /// Fetches the vector and borrows its elements as a slice.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the fetched elements.
+    pub fn as_slice(&mut self) -> &[T] {
+        self.fetch()
+    }
+
+    /// Fetches the vector and borrows its elements as a mutable slice.
+    ///
+    /// # Returns
+    ///
+    /// A mutable slice over the fetched elements.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.fetch()
+    }
+
+    /// Fetches the vector and borrows its elements as a slice, failing
+    /// instead of panicking if the vector is poisoned.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the fetched elements, or the poison of the vector.
+    pub fn try_as_slice(&mut self) -> Result<&[T], &Poisoned> {
+        self.try_fetch().map(|vec| vec.as_slice())
+    }
+
+    /// Fetches the vector and borrows its elements as a mutable slice,
+    /// failing instead of panicking if the vector is poisoned.
+    ///
+    /// # Returns
+    ///
+    /// A mutable slice over the fetched elements, or the poison of the vector.
+    pub fn try_as_mut_slice(&mut self) -> Result<&mut [T], &Poisoned> {
+        self.try_fetch().map(|vec| vec.as_mut_slice())
+    }
+
+    /// Returns an iterator over references to the elements.
+    ///
+    /// The vector is fetched when the first element is pulled, not here.
+    ///
+    /// # Returns
+    ///
+    /// A lazy iterator borrowing the `DeferredVec`.
+    pub fn iter(&mut self) -> iter::Iter<'_, T, F> {
+        iter::Iter::new(self)
+    }
+
+    /// Returns an iterator over mutable references to the elements.
+    ///
+    /// The vector is fetched when the first element is pulled, not here.
+    ///
+    /// # Returns
+    ///
+    /// A lazy iterator borrowing the `DeferredVec` mutably.
+    pub fn iter_mut(&mut self) -> iter::IterMut<'_, T, F> {
+        iter::IterMut::new(self)
     }
 
-    /// Returns the length of the fetched vector.
+    /// Returns the length of the vector.
     ///
-    /// This method fetches the vector and returns its length.
-    /// It panics if `fetch` returns `None`.
+    /// While the vector is deferred (or stale), the `len_function` is asked
+    /// if there is one, then the `size_hint` of the fetcher. Otherwise, this
+    /// method fetches the vector and returns its length.
     ///
     /// # Returns
     ///
-    /// The length of the fetched vector.
+    /// The length of the vector.
     pub fn len(&mut self) -> usize {
-        if let Some(vec) = self.fetch() {
-            return vec.len();
+        if self.vec.is_none() || self.is_stale() {
+            if let Some(len) = self.len_hint() {
+                return len;
+            }
+        }
+        self.fetch().len()
+    }
+
+    /// Returns the length of the vector if it is known without fetching.
+    ///
+    /// A prefetch in flight is waited for, unless there is a `len_function`.
+    ///
+    /// # Returns
+    ///
+    /// The length of the fresh fetched vector, else the result of the
+    /// `len_function` or the `size_hint` of the fetcher.
+    pub(crate) fn len_hint(&mut self) -> Option<usize> {
+        if self.vec.is_none() || self.is_stale() {
+            if let Some(len_function) = &mut self.len_function {
+                return Some(len_function());
+            }
+        }
+        self.collect_prefetch();
+        match &self.vec {
+            Some(vec) if !self.is_stale() => Some(vec.len()),
+            _ => self.fetcher().size_hint(),
         }
-        panic!("Should not happen");
+    }
+
+    /// Checks if the vector is empty.
+    ///
+    /// While the vector is deferred, the answer comes from the length hint,
+    /// the `len_function` or the `size_hint` of the fetcher, without
+    /// fetching. Otherwise, this method fetches the vector, like `len`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the vector has no elements.
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
     }
 
     /// Checks if the vector is initialized.
     ///
     /// # Returns
     ///
-    /// `true` if `vec` is `None` (not yet fetched) and `false` otherwise.
+    /// `true` if `vec` is `None` (not yet fetched) and no prefetch is in
+    /// flight, and `false` otherwise.
     pub fn is_deferred(&self) -> bool {
-        self.vec.is_none()
+        self.vec.is_none() && self.prefetch.is_none()
+    }
+
+    /// Checks if a caught panic of the `fetch_function` poisoned the vector.
+    ///
+    /// # Returns
+    ///
+    /// `true` if accesses fail until `clear_poison` is called.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.is_some()
+    }
+
+    /// Clears the poison, so the next access fetches again.
+    ///
+    /// # Returns
+    ///
+    /// The cleared poison, or `None` if the vector was not poisoned.
+    pub fn clear_poison(&mut self) -> Option<Poisoned> {
+        self.poison.take()
+    }
+
+    /// Checks if a prefetch is in flight.
+    ///
+    /// The vector stays loading until an access collects the result, even
+    /// if the worker thread has already returned.
+    ///
+    /// # Returns
+    ///
+    /// `true` if `prefetch` was called and no access has collected its result.
+    pub fn is_loading(&self) -> bool {
+        self.prefetch.is_some()
+    }
+
+    /// Checks if the fetched vector has outlived its time-to-live.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the vector is fetched, a time-to-live is set and it has
+    /// expired, and `false` otherwise.
+    pub fn is_stale(&self) -> bool {
+        self.expires_in() == Some(Duration::ZERO)
+    }
+
+    /// Returns how long the fetched vector stays fresh.
+    ///
+    /// # Returns
+    ///
+    /// The remaining time before expiry (zero once expired), or `None` if
+    /// the vector is deferred, no time-to-live is set, or the time-to-live
+    /// is too long to ever expire (such as `Duration::MAX`).
+    pub fn expires_in(&self) -> Option<Duration> {
+        let expires_at = self.fetched_at?.checked_add(self.ttl?)?;
+        Some(expires_at.saturating_duration_since(self.clock.now()))
+    }
+
+    /// Returns the name identifying the vector in diagnostics.
+    ///
+    /// # Returns
+    ///
+    /// The name set with `with_name`, or else the `name` of the fetcher
+    /// (its type name while a prefetch holds it).
+    pub fn name(&self) -> &str {
+        match (&self.name, &self.fetch_function) {
+            (Some(name), _) => name,
+            (None, Some(fetch_function)) => fetch_function.name(),
+            (None, None) => std::any::type_name::<F>(),
+        }
+    }
+
+    /// Returns the fetch counters and timings, without fetching.
+    pub fn stats(&self) -> &FetchStats {
+        &self.stats
+    }
+
+    /// Borrows the fetched vector without fetching it.
+    ///
+    /// A stale vector is still returned.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the fetched elements, or `None` if the vector is deferred.
+    pub fn loaded(&self) -> Option<&[T]> {
+        self.vec.as_deref()
+    }
+
+    /// Drops the fetched vector and returns to the deferred state.
+    ///
+    /// The `fetch_function` is kept, so the next access fetches again. A
+    /// prefetch in flight is waited for, and its result dropped.
+    pub fn reset(&mut self) {
+        self.collect_prefetch();
+        if let Some(vec) = self.vec.take() {
+            trace::reset(self.name(), vec.len());
+            self.hooks.on_evict(&vec);
+        }
+        self.fetched_at = None;
+    }
+
+    /// Drops the fetched vector, if any, and fetches it again right away.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the newly fetched vector.
+    pub fn reload(&mut self) -> &Vec<T> {
+        self.reset();
+        self.fetch()
+    }
+
+    /// Moves the fetched vector out, leaving `DeferredVec` deferred.
+    ///
+    /// This method does not fetch: a deferred vector yields `None`. A
+    /// prefetch in flight is waited for, and its result taken.
+    ///
+    /// # Returns
+    ///
+    /// The fetched vector, or `None` if it was deferred.
+    pub fn take(&mut self) -> Option<Vec<T>> {
+        self.collect_prefetch();
+        self.fetched_at = None;
+        self.vec.take()
+    }
+
+    /// Installs `vec` as the fetched vector without calling the `fetch_function`.
+    ///
+    /// The installed vector counts as freshly fetched for the time-to-live.
+    /// A prefetch in flight is waited for, and its result replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `vec` - The vector to install.
+    ///
+    /// # Returns
+    ///
+    /// The previously fetched vector, or `None` if it was deferred.
+    pub fn replace(&mut self, vec: Vec<T>) -> Option<Vec<T>> {
+        self.collect_prefetch();
+        self.fetched_at = Some(self.clock.now());
+        self.vec.replace(vec)
+    }
+}
+
+/// Background prefetch.
+///
+/// The fetcher and the elements move to a worker thread, hence the `Send`
+/// and `'static` bounds.
+impl<T, F> DeferredVec<T, F>
+where
+    T: Send + 'static,
+    F: Fetcher<T> + Send + 'static,
+{
+    /// Starts fetching the vector on a background thread and returns
+    /// immediately.
+    ///
+    /// The next access takes the result, waiting for the worker if it is
+    /// still running; the `fetch_function` is never called twice for one
+    /// prefetch. Does nothing if the vector is fetched and fresh, poisoned,
+    /// or if a prefetch is already in flight. The before-fetch hooks run on the
+    /// calling thread, the after-fetch hooks on the access collecting the
+    /// result.
+    pub fn prefetch(&mut self) {
+        if self.is_stale() {
+            self.reset();
+        }
+        if self.vec.is_none() && self.prefetch.is_none() && self.poison.is_none() {
+            self.hooks.before_fetch();
+            let fetch_function = self.fetch_function.take().unwrap();
+            self.prefetch = Some(Prefetch::spawn(fetch_function));
+        }
+    }
+}
+
+/// Lazy combinators, deriving deferred vectors from `DeferredVec`.
+///
+/// Each combinator consumes the upstream vectors and returns a deferred
+/// vector whose fetch forces them and moves their elements through the
+/// transformation.
+impl<T, F> DeferredVec<T, F>
+where
+    F: Fetcher<T>,
+{
+    /// Derives a deferred vector applying `f` to each element.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The function applied to each element of this vector.
+    ///
+    /// # Returns
+    ///
+    /// A deferred vector of the results of `f`, as long as this vector.
+    pub fn map<U, G>(self, f: G) -> DeferredVec<U, combinator::Map<T, F, G>>
+    where
+        G: FnMut(T) -> U,
+    {
+        DeferredVec::new(combinator::Map::new(self, f))
+    }
+
+    /// Derives a deferred vector keeping the elements matching `predicate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - Returns `true` for the elements to keep.
+    ///
+    /// # Returns
+    ///
+    /// A deferred vector of the matching elements, in order.
+    pub fn filter<P>(self, predicate: P) -> DeferredVec<T, combinator::Filter<T, F, P>>
+    where
+        P: FnMut(&T) -> bool,
+    {
+        DeferredVec::new(combinator::Filter::new(self, predicate))
+    }
+
+    /// Derives a deferred vector pairing the elements of two vectors.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The vector providing the second element of each pair.
+    ///
+    /// # Returns
+    ///
+    /// A deferred vector of pairs, as long as the shorter vector.
+    pub fn zip<U, G>(
+        self,
+        other: DeferredVec<U, G>,
+    ) -> DeferredVec<(T, U), combinator::Zip<T, F, U, G>>
+    where
+        G: Fetcher<U>,
+    {
+        DeferredVec::new(combinator::Zip::new(self, other))
+    }
+
+    /// Derives a deferred vector appending the elements of `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The vector whose elements follow the elements of this one.
+    ///
+    /// # Returns
+    ///
+    /// A deferred vector of the elements of both vectors.
+    pub fn chain<G>(self, other: DeferredVec<T, G>) -> DeferredVec<T, combinator::Chain<T, F, G>>
+    where
+        G: Fetcher<T>,
+    {
+        DeferredVec::new(combinator::Chain::new(self, other))
+    }
+}
+
+/// Methods returning owned copies of the vector.
+///
+/// The generic type `T` is bound by the trait `std::clone::Clone` to ensure
+/// that elements of the vector can be cloned.
+impl<T, F> DeferredVec<T, F>
+where
+    T: std::clone::Clone,
+    F: Fetcher<T>,
+{
+    /// Fetches and returns a copy of the vector.
+    ///
+    /// Prefer `as_slice` or `force` when a borrow is enough, as they do not
+    /// clone the elements.
+    ///
+    /// # Returns
+    ///
+    /// The fetched vector.
+    pub fn get(&mut self) -> Vec<T> {
+        self.fetch().clone()
+    }
+}
+
+/// Constructors for one-shot fetch sources.
+impl<T> DeferredVec<T> {
+    /// Constructs a new instance of `DeferredVec` from a one-shot loader.
+    ///
+    /// The loader is consumed by the first fetch. It panics if the vector
+    /// ever needs to be fetched a second time, for example after `reset`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetch_function` - An `FnOnce` closure to initialize the vector.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `DeferredVec` with `vec` initialized as `None`.
+    pub fn new_once<G>(fetch_function: G) -> DeferredVec<T, impl FnMut() -> Vec<T>>
+    where
+        G: FnOnce() -> Vec<T>,
+    {
+        let mut fetch_function = Some(fetch_function);
+        DeferredVec::new(move || {
+            let fetch_function = fetch_function
+                .take()
+                .expect("one-shot fetch function called more than once");
+            fetch_function()
+        })
+    }
+}
+
+/// The `fetch_function` of a `DeferredVec` built from a vector, which has
+/// no source to fetch the elements again from.
+fn no_source<T>() -> Vec<T> {
+    panic!("this DeferredVec was built from a vector and cannot be fetched again")
+}
+
+/// Builds a fetched `DeferredVec` from a vector.
+///
+/// The vector is its only source: fetching again, for example after
+/// `reset`, `reload` or the expiry of a time-to-live, panics instead of
+/// returning an empty vector.
+impl<T> From<Vec<T>> for DeferredVec<T> {
+    fn from(vec: Vec<T>) -> DeferredVec<T> {
+        let mut deferred_vec = DeferredVec::new(no_source as fn() -> Vec<T>);
+        deferred_vec.replace(vec);
+        deferred_vec
     }
 }
 
@@ -152,6 +1056,7 @@ mod tests {
     ///
     /// This test ensures that the vector is initially deferred,
     /// and after calling `get`, it is no longer deferred and contains the correct values.
+    #[allow(clippy::bool_assert_comparison)]
     fn it_works() {
         let mut tst = DeferredVec::new(|| vec![1, 2, 3]);
         assert_eq!(tst.is_deferred(), true);
@@ -159,4 +1064,264 @@ mod tests {
         assert_eq!(tst.is_deferred(), false);
         assert_eq!(v, vec![1, 2, 3]);
     }
+
+    #[test]
+    #[should_panic(expected = "built from a vector and cannot be fetched again")]
+    /// Tests that a `DeferredVec` built from a vector refuses to be fetched
+    /// again rather than turning empty.
+    fn from_vec_cannot_refetch() {
+        let mut tst = DeferredVec::from(vec![1, 2, 3]);
+        assert_eq!(tst.as_slice(), &[1, 2, 3]);
+        tst.reset();
+        tst.as_slice();
+    }
+
+    #[test]
+    /// Tests that capturing `FnMut` and `FnOnce` closures can be used as fetch sources.
+    fn capturing_closures() {
+        let path = String::from("data.txt");
+        let mut calls = 0;
+        let mut tst = DeferredVec::new(|| {
+            calls += 1;
+            vec![path.clone()]
+        });
+        assert_eq!(tst.get(), vec![String::from("data.txt")]);
+        assert_eq!(tst.len(), 1);
+        drop(tst);
+        assert_eq!(calls, 1);
+
+        let owned = vec![4, 5, 6];
+        let mut tst = DeferredVec::new_once(move || owned);
+        assert!(tst.is_deferred());
+        assert_eq!(tst.get(), vec![4, 5, 6]);
+    }
+
+    #[test]
+    /// Tests borrowed access, which fetches once and never clones.
+    fn borrowed_access() {
+        let mut tst = DeferredVec::new(|| vec![1, 2, 3]);
+        assert_eq!(tst.as_slice(), &[1, 2, 3]);
+        tst.as_mut_slice()[0] = 10;
+        tst.force_mut().push(4);
+        assert_eq!(tst.force(), &vec![10, 2, 3, 4]);
+        assert_eq!(tst.len(), 4);
+    }
+
+    #[test]
+    /// Tests that non-cloneable elements can be stored and borrowed.
+    fn non_clone_elements() {
+        let mut tst: DeferredVec<Box<dyn Fn() -> i32>> =
+            DeferredVec::new(|| vec![Box::new(|| 1), Box::new(|| 2)]);
+        assert!(tst.is_deferred());
+        let total: i32 = tst.as_slice().iter().map(|f| f()).sum();
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    /// Tests invalidation with `reset`, `reload`, `take` and `replace`.
+    fn invalidation() {
+        let mut fetches = 0;
+        let mut tst = DeferredVec::new(|| {
+            fetches += 1;
+            vec![fetches]
+        });
+        assert_eq!(tst.take(), None);
+        assert_eq!(tst.as_slice(), &[1]);
+        tst.reset();
+        assert!(tst.is_deferred());
+        assert_eq!(tst.as_slice(), &[2]);
+        assert_eq!(tst.reload(), &vec![3]);
+
+        assert_eq!(tst.replace(vec![10, 20]), Some(vec![3]));
+        assert_eq!(tst.as_slice(), &[10, 20]);
+        assert_eq!(tst.take(), Some(vec![10, 20]));
+        assert!(tst.is_deferred());
+        assert_eq!(tst.as_slice(), &[4]);
+    }
+
+    #[test]
+    /// Tests that access after the time-to-live refetches, using a manual clock.
+    fn ttl_expiry() {
+        let clock = clock::ManualClock::new();
+        let mut fetches = 0;
+        let mut tst = DeferredVec::new(|| {
+            fetches += 1;
+            vec![fetches]
+        })
+        .with_ttl(Duration::from_secs(60))
+        .with_clock(clock.clone());
+        assert_eq!(tst.expires_in(), None);
+        assert_eq!(tst.as_slice(), &[1]);
+        assert_eq!(tst.expires_in(), Some(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(59));
+        assert!(!tst.is_stale());
+        assert_eq!(tst.as_slice(), &[1]);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(tst.is_stale());
+        assert_eq!(tst.expires_in(), Some(Duration::ZERO));
+        assert_eq!(tst.as_slice(), &[2]);
+        assert!(!tst.is_stale());
+    }
+
+    #[test]
+    /// Tests that a time-to-live too long to represent never expires.
+    fn ttl_never_expires() {
+        let mut tst = DeferredVec::new(|| vec![1]).with_ttl(Duration::MAX);
+        assert_eq!(tst.as_slice(), &[1]);
+        assert_eq!(tst.as_slice(), &[1]);
+        assert!(!tst.is_stale());
+        assert_eq!(tst.expires_in(), None);
+        assert_eq!(tst.stats().fetch_count, 1);
+    }
+
+    #[test]
+    /// Tests the fetch counters and timings, measured with a manual clock.
+    fn fetch_stats() {
+        let clock = clock::ManualClock::new();
+        let fetch_clock = clock.clone();
+        let mut tst = DeferredVec::new(move || {
+            fetch_clock.advance(Duration::from_millis(5));
+            vec![1, 2, 3]
+        })
+        .with_clock(clock);
+        assert_eq!(tst.stats(), &FetchStats::default());
+
+        tst.as_slice();
+        tst.as_slice();
+        assert_eq!(tst.len(), 3);
+        tst.reload();
+        let stats = tst.stats();
+        assert_eq!(stats.fetch_count, 2);
+        assert_eq!(stats.cache_hits, 2);
+        assert_eq!(stats.last_fetch_len, 3);
+        assert_eq!(stats.last_fetch_duration, Some(Duration::from_millis(5)));
+        assert_eq!(stats.total_fetch_duration, Duration::from_millis(10));
+        assert!(stats.last_loaded_at.is_some());
+    }
+
+    #[test]
+    /// Tests that lifecycle hooks run in order around fetches and evictions.
+    fn lifecycle_hooks() {
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (first, second, after, evict) = (
+            Arc::clone(&log),
+            Arc::clone(&log),
+            Arc::clone(&log),
+            Arc::clone(&log),
+        );
+        let mut tst = DeferredVec::new(|| vec![1, 2, 3])
+            .with_before_fetch_hook(move || first.lock().unwrap().push(String::from("before 1")))
+            .with_before_fetch_hook(move || second.lock().unwrap().push(String::from("before 2")))
+            .with_after_fetch_hook(move |vec| {
+                after.lock().unwrap().push(format!("after {}", vec.len()))
+            })
+            .with_evict_hook(move |vec| evict.lock().unwrap().push(format!("evict {}", vec.len())));
+
+        tst.as_slice();
+        tst.as_slice();
+        tst.reset();
+        tst.reset();
+        tst.replace(vec![4]);
+        tst.take();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["before 1", "before 2", "after 3", "evict 3"]
+        );
+    }
+
+    #[test]
+    /// Tests that `len` uses the length function while deferred.
+    fn len_function() {
+        let mut tst = DeferredVec::new(|| vec![1, 2, 3]).with_len_function(|| 3);
+        assert_eq!(tst.len(), 3);
+        assert!(!tst.is_empty());
+        assert!(tst.is_deferred());
+        tst.force_mut().push(4);
+        assert_eq!(tst.len(), 4);
+    }
+
+    #[test]
+    /// Tests a third-party fetcher carrying configuration and a size hint.
+    fn custom_fetcher() {
+        struct Countdown {
+            from: u32,
+        }
+
+        impl Fetcher<u32> for Countdown {
+            fn fetch_all(&mut self) -> Vec<u32> {
+                (1..=self.from).rev().collect()
+            }
+
+            fn size_hint(&mut self) -> Option<usize> {
+                Some(self.from as usize)
+            }
+        }
+
+        let mut tst = DeferredVec::new(Countdown { from: 3 });
+        assert_eq!(tst.len(), 3);
+        assert!(tst.is_deferred());
+        assert_eq!(tst.as_slice(), &[3, 2, 1]);
+
+        let mut tst = DeferredVec::new(fetcher::FromIter::new(vec!['a', 'b']));
+        assert_eq!(tst.len(), 2);
+        assert_eq!(tst.get(), vec!['a', 'b']);
+    }
+
+    #[test]
+    /// Tests that a prefetch runs the loader once, in the background, and
+    /// that the next access waits for it.
+    fn prefetch() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{mpsc, Arc};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let (gate, wait) = mpsc::channel();
+        let mut tst = DeferredVec::new(move || {
+            wait.recv().unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            vec![1, 2, 3]
+        });
+
+        tst.prefetch();
+        assert!(tst.is_loading());
+        assert!(!tst.is_deferred());
+        assert_eq!(tst.loaded(), None);
+        tst.prefetch();
+
+        gate.send(()).unwrap();
+        assert_eq!(tst.as_slice(), &[1, 2, 3]);
+        assert!(!tst.is_loading());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(tst.stats().fetch_count, 1);
+        assert_eq!(tst.stats().cache_hits, 0);
+
+        tst.prefetch();
+        assert!(!tst.is_loading());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    /// Tests that a panic during a prefetch reaches the access and leaves
+    /// the loader in place.
+    fn prefetch_panic() {
+        let mut attempts = 0;
+        let mut tst = DeferredVec::new(move || {
+            attempts += 1;
+            if attempts == 1 {
+                panic!("first attempt fails");
+            }
+            vec![attempts]
+        });
+
+        tst.prefetch();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tst.len()));
+        assert!(result.is_err());
+        assert!(tst.is_deferred());
+        assert_eq!(tst.as_slice(), &[2]);
+    }
 }
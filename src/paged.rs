@@ -0,0 +1,297 @@
+//! Paged deferred vectors.
+//!
+//! `PagedDeferredVec` splits the vector into fixed-size pages and fetches a
+//! page only when one of its elements is accessed, so reading `v[10]` does
+//! not load the whole vector.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// A lazily initialized vector loaded one page at a time.
+///
+/// This struct holds the loaded pages, keyed by their index, the page size,
+/// the total length when it is known, and a `fetch_page` function returning
+/// the elements of the page with the given index.
+pub struct PagedDeferredVec<T, F = fn(usize) -> Vec<T>> {
+    pages: BTreeMap<usize, Vec<T>>,
+    page_size: usize,
+    len: Option<usize>,
+    fetch_page: F,
+}
+
+/// Implement methods for `PagedDeferredVec`.
+impl<T, F> PagedDeferredVec<T, F>
+where
+    F: FnMut(usize) -> Vec<T>,
+{
+    /// Constructs a new instance of `PagedDeferredVec` with a known length.
+    ///
+    /// It panics if `page_size` is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The total number of elements.
+    /// * `page_size` - The number of elements in each page.
+    /// * `fetch_page` - A function returning the elements of a page, given its index.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `PagedDeferredVec` with no page loaded.
+    pub fn new(len: usize, page_size: usize, fetch_page: F) -> PagedDeferredVec<T, F> {
+        let mut paged = PagedDeferredVec::with_unknown_len(page_size, fetch_page);
+        paged.len = Some(len);
+        paged
+    }
+
+    /// Constructs a new instance of `PagedDeferredVec` whose length is discovered lazily.
+    ///
+    /// The end of the vector is the first page holding fewer than
+    /// `page_size` elements. It panics if `page_size` is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_size` - The number of elements in each page.
+    /// * `fetch_page` - A function returning the elements of a page, given its index.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `PagedDeferredVec` with no page loaded.
+    pub fn with_unknown_len(page_size: usize, fetch_page: F) -> PagedDeferredVec<T, F> {
+        assert!(page_size > 0, "page size must be greater than zero");
+        PagedDeferredVec {
+            pages: BTreeMap::new(),
+            page_size,
+            len: None,
+            fetch_page,
+        }
+    }
+
+    /// Fetches the page with the given index if it is not loaded yet.
+    ///
+    /// A short, non-empty page reveals the length of the vector, and so does
+    /// an empty page that is the first one or follows a loaded page. An empty
+    /// page lies past the end and is not kept.
+    ///
+    /// # Returns
+    ///
+    /// The elements of the page.
+    fn fetch(&mut self, page: usize) -> &[T] {
+        if !self.pages.contains_key(&page) {
+            let items = (self.fetch_page)(page);
+            if self.len.is_none()
+                && items.len() < self.page_size
+                && (page == 0 || !items.is_empty() || self.pages.contains_key(&(page - 1)))
+            {
+                self.len = Some(page * self.page_size + items.len());
+            }
+            if items.is_empty() {
+                return &[];
+            }
+            self.pages.insert(page, items);
+        }
+        &self.pages[&page]
+    }
+
+    /// Returns the element at `index`, fetching only its page.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the element.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the element, or `None` if `index` is out of bounds.
+    pub fn get(&mut self, index: usize) -> Option<&T> {
+        if self.len.is_some_and(|len| index >= len) {
+            return None;
+        }
+        let page_size = self.page_size;
+        self.fetch(index / page_size).get(index % page_size)
+    }
+
+    /// Iterates over the elements in `range`, fetching only the pages it touches.
+    ///
+    /// The range is truncated at the end of the vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The positions of the elements.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over references to the elements.
+    pub fn range(&mut self, range: Range<usize>) -> impl Iterator<Item = &T> {
+        let mut end = match self.len {
+            Some(len) => range.end.min(len),
+            None => range.end,
+        };
+        if range.start < end {
+            for page in range.start / self.page_size..=(end - 1) / self.page_size {
+                let loaded = self.fetch(page).len();
+                if loaded < self.page_size {
+                    end = end.min(page * self.page_size + loaded);
+                    break;
+                }
+            }
+        }
+        let (pages, page_size) = (&self.pages, self.page_size);
+        (range.start..end.max(range.start))
+            .map(move |index| &pages[&(index / page_size)][index % page_size])
+    }
+
+    /// Returns the length of the vector.
+    ///
+    /// If the length is not known yet, pages are fetched in order until the
+    /// last one is found.
+    ///
+    /// # Returns
+    ///
+    /// The number of elements.
+    pub fn len(&mut self) -> usize {
+        if let Some(len) = self.len {
+            return len;
+        }
+        let mut page = 0;
+        loop {
+            let loaded = self.fetch(page).len();
+            if loaded < self.page_size {
+                let len = page * self.page_size + loaded;
+                self.len = Some(len);
+                return len;
+            }
+            page += 1;
+        }
+    }
+
+    /// Checks if the vector is empty.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the vector has no elements.
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements in each page.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Checks if the page with the given index has been fetched.
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - The index of the page.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the page is loaded and `false` otherwise.
+    pub fn is_page_loaded(&self, page: usize) -> bool {
+        self.pages.contains_key(&page)
+    }
+
+    /// Returns the number of pages fetched so far.
+    pub fn loaded_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Checks if no page has been fetched yet.
+    ///
+    /// # Returns
+    ///
+    /// `true` if no page is loaded and `false` otherwise.
+    pub fn is_deferred(&self) -> bool {
+        self.loaded_pages() == 0
+    }
+}
+
+/// Unit tests for `PagedDeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Tests that indexing and ranges only fetch the pages they touch.
+    fn fetches_touched_pages() {
+        let mut fetched = Vec::new();
+        let mut tst = PagedDeferredVec::new(10, 3, |page| {
+            fetched.push(page);
+            (page * 3..(page * 3 + 3).min(10)).collect::<Vec<usize>>()
+        });
+        assert!(tst.is_deferred());
+        assert_eq!(tst.len(), 10);
+        assert_eq!(tst.get(4), Some(&4));
+        assert!(tst.is_page_loaded(1));
+        assert_eq!(tst.loaded_pages(), 1);
+
+        assert_eq!(
+            tst.range(2..7).copied().collect::<Vec<_>>(),
+            vec![2, 3, 4, 5, 6]
+        );
+        assert_eq!(tst.loaded_pages(), 3);
+        assert!(!tst.is_page_loaded(3));
+        assert_eq!(tst.get(10), None);
+        drop(tst);
+        assert_eq!(fetched, vec![1, 0, 2]);
+    }
+
+    #[test]
+    /// Tests that ranges past the known length never fetch missing pages.
+    fn range_stops_at_len() {
+        let mut tst = PagedDeferredVec::new(10, 5, |page: usize| {
+            assert!(page < 2, "page {} is past the end", page);
+            (page * 5..page * 5 + 5).collect::<Vec<usize>>()
+        });
+        assert_eq!(tst.range(0..40).count(), 10);
+        assert_eq!(tst.range(8..40).copied().collect::<Vec<_>>(), vec![8, 9]);
+        assert_eq!(tst.range(10..40).count(), 0);
+        assert_eq!(tst.range(30..40).count(), 0);
+        assert_eq!(tst.loaded_pages(), 2);
+    }
+
+    #[test]
+    /// Tests discovering the length from the first short page.
+    fn discovers_unknown_len() {
+        let fetch_page = |page: usize| (page * 4..(page * 4 + 4).min(10)).collect::<Vec<usize>>();
+        let mut tst = PagedDeferredVec::with_unknown_len(4, fetch_page);
+        assert_eq!(tst.get(20), None);
+        assert_eq!(tst.loaded_pages(), 0);
+        assert_eq!(tst.range(8..100).count(), 2);
+        assert_eq!(tst.loaded_pages(), 1);
+        assert_eq!(tst.len(), 10);
+        assert_eq!(tst.loaded_pages(), 1);
+
+        let mut tst = PagedDeferredVec::with_unknown_len(4, fetch_page);
+        assert_eq!(tst.len(), 10);
+        assert_eq!(tst.loaded_pages(), 3);
+    }
+
+    #[test]
+    /// Tests that indices far past the end of a vector of unknown length
+    /// neither allocate nor mark pages as loaded.
+    fn huge_index_past_unknown_len() {
+        let mut fetched = Vec::new();
+        let mut tst = PagedDeferredVec::with_unknown_len(4, |page: usize| {
+            fetched.push(page);
+            if page == 0 {
+                vec![0, 1, 2, 3]
+            } else {
+                Vec::new()
+            }
+        });
+        assert_eq!(tst.get(usize::MAX / 2), None);
+        assert_eq!(tst.get(4), None);
+        assert_eq!(tst.range(1 << 33..usize::MAX).count(), 0);
+        assert!(!tst.is_page_loaded(1));
+        assert!(tst.is_deferred());
+
+        assert_eq!(
+            tst.range(2..usize::MAX).copied().collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(tst.loaded_pages(), 1);
+        assert_eq!(tst.len(), 4);
+        drop(tst);
+        assert_eq!(fetched, vec![usize::MAX / 8, 1, 1 << 31, 0, 1]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Panic policies.
+//!
+//! By default, a panic of the `fetch_function` unwinds through the access
+//! that triggered the fetch, and the `DeferredVec` stays deferred. A
+//! `PanicPolicy` can instead catch the panic and poison the vector, so that
+//! later accesses fail with `Poisoned` until the poison is cleared, or retry
+//! the fetch a number of times before poisoning it.
+
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+
+/// What `DeferredVec` does when its `fetch_function` panics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// The panic unwinds through the access, and the vector stays deferred.
+    #[default]
+    Propagate,
+    /// The panic is caught, and the vector is poisoned.
+    Poison,
+    /// The panic is caught, and the fetch is retried up to the given number
+    /// of times before the vector is poisoned.
+    Retry(u32),
+}
+
+impl PanicPolicy {
+    /// Returns how many times a panicking fetch is retried.
+    pub(crate) fn retries(self) -> u32 {
+        match self {
+            PanicPolicy::Retry(retries) => retries,
+            PanicPolicy::Propagate | PanicPolicy::Poison => 0,
+        }
+    }
+}
+
+/// The error of an access to a poisoned `DeferredVec`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Poisoned {
+    message: Option<String>,
+}
+
+impl Poisoned {
+    /// Constructs a new instance of `Poisoned` from a panic payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload caught from the `fetch_function`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Poisoned` keeping the panic message, if any.
+    pub(crate) fn new(payload: Box<dyn Any + Send>) -> Poisoned {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => Some(*message),
+            Err(payload) => payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string()),
+        };
+        Poisoned { message }
+    }
+
+    /// Returns the message of the panic, if it was a string.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "the fetch function panicked: {}", message),
+            None => write!(f, "the fetch function panicked"),
+        }
+    }
+}
+
+impl Error for Poisoned {}
+
+/// Unit tests for the panic policies of `DeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DeferredVec;
+    use std::cell::Cell;
+
+    #[test]
+    /// Tests that a caught panic poisons the vector until the poison is cleared.
+    fn poison_and_clear() {
+        let calls = Cell::new(0);
+        let mut tst = DeferredVec::new(|| {
+            calls.set(calls.get() + 1);
+            if calls.get() == 1 {
+                panic!("database offline");
+            }
+            vec![1, 2]
+        })
+        .with_panic_policy(PanicPolicy::Poison);
+
+        let poisoned = tst.try_as_slice().unwrap_err();
+        assert_eq!(poisoned.message(), Some("database offline"));
+        assert_eq!(
+            poisoned.to_string(),
+            "the fetch function panicked: database offline"
+        );
+        assert!(tst.is_poisoned());
+        assert!(tst.try_as_mut_slice().is_err());
+        assert_eq!(calls.get(), 1);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tst.get()));
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+
+        assert!(tst.clear_poison().is_some());
+        assert!(!tst.is_poisoned());
+        assert_eq!(tst.try_as_slice(), Ok(&[1, 2][..]));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    /// Tests that panicking fetches are retried, then poison the vector.
+    fn retry() {
+        let calls = Cell::new(0);
+        let mut tst = DeferredVec::new(|| {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                panic!("attempt {}", calls.get());
+            }
+            vec![calls.get()]
+        })
+        .with_panic_policy(PanicPolicy::Retry(2));
+        assert_eq!(tst.as_slice(), &[3]);
+        assert_eq!(tst.stats().fetch_count, 1);
+
+        calls.set(0);
+        tst.reset();
+        tst = tst.with_panic_policy(PanicPolicy::Retry(1));
+        assert_eq!(tst.try_as_slice().unwrap_err().message(), Some("attempt 2"));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    /// Tests the panic policy of a prefetch.
+    fn prefetch_poison() {
+        let mut tst = DeferredVec::new(|| -> Vec<u8> { panic!("no data") })
+            .with_panic_policy(PanicPolicy::Poison);
+        tst.prefetch();
+        assert!(tst.try_as_slice().is_err());
+        assert!(tst.is_poisoned());
+        assert!(!tst.is_loading());
+        tst.prefetch();
+        assert!(!tst.is_loading());
+    }
+}
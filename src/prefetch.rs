@@ -0,0 +1,70 @@
+//! Background prefetch.
+//!
+//! `DeferredVec::prefetch` moves the fetcher to a worker thread, which
+//! calls `fetch_all` and hands both the fetcher and the result back. The
+//! next access of the `DeferredVec` waits for the worker if it is still
+//! running, then installs the result, so a prefetched vector is never
+//! fetched twice. A panic of the fetcher is caught on the worker and
+//! resumed by the access collecting the result.
+
+use crate::Fetcher;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The outcome of a prefetch.
+pub(crate) struct Prefetched<T, F> {
+    /// The fetcher, handed back to the `DeferredVec`.
+    pub(crate) fetch_function: F,
+    /// The fetched vector, or the payload of the fetcher's panic.
+    pub(crate) result: thread::Result<Vec<T>>,
+    /// The time spent in `fetch_all`.
+    pub(crate) duration: Duration,
+}
+
+/// A fetch running on a worker thread.
+pub(crate) struct Prefetch<T, F> {
+    handle: JoinHandle<Prefetched<T, F>>,
+}
+
+impl<T, F> Prefetch<T, F>
+where
+    T: Send + 'static,
+    F: Fetcher<T> + Send + 'static,
+{
+    /// Starts fetching on a new worker thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetch_function` - The fetcher, moved to the worker.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the running fetch.
+    pub(crate) fn spawn(mut fetch_function: F) -> Prefetch<T, F> {
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| fetch_function.fetch_all()));
+            Prefetched {
+                fetch_function,
+                result,
+                duration: started.elapsed(),
+            }
+        });
+        Prefetch { handle }
+    }
+}
+
+impl<T, F> Prefetch<T, F> {
+    /// Waits for the worker to return.
+    ///
+    /// # Returns
+    ///
+    /// The fetcher and the outcome of its fetch.
+    pub(crate) fn wait(self) -> Prefetched<T, F> {
+        match self.handle.join() {
+            Ok(prefetched) => prefetched,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
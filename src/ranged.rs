@@ -0,0 +1,295 @@
+//! Range-loaded deferred vectors.
+//!
+//! `RangedDeferredVec` is backed by a `fetch_range` function that can return
+//! any window of the vector. Accessing a slice fetches only the parts of it
+//! that are not loaded yet, and adjacent loaded ranges are merged so the
+//! coverage stays compact.
+
+use crate::Fetcher;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// A lazily initialized vector loaded by arbitrary ranges.
+///
+/// This struct holds the loaded segments, keyed by their start index and
+/// never overlapping or touching each other, the total length, and a
+/// `fetch_range` function returning the elements in the given range.
+pub struct RangedDeferredVec<T, F = fn(Range<usize>) -> Vec<T>> {
+    segments: BTreeMap<usize, Vec<T>>,
+    len: usize,
+    fetch_range: F,
+}
+
+/// Implement methods for `RangedDeferredVec`.
+impl<T, F> RangedDeferredVec<T, F>
+where
+    F: FnMut(Range<usize>) -> Vec<T>,
+{
+    /// Constructs a new instance of `RangedDeferredVec`.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The total number of elements.
+    /// * `fetch_range` - A function returning exactly the elements in the given range.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `RangedDeferredVec` with nothing loaded.
+    pub fn new(len: usize, fetch_range: F) -> RangedDeferredVec<T, F> {
+        RangedDeferredVec {
+            segments: BTreeMap::new(),
+            len,
+            fetch_range,
+        }
+    }
+
+    /// Calls `fetch_range`, checking it returned one element per index.
+    fn fetch(&mut self, range: Range<usize>) -> Vec<T> {
+        let expected = range.len();
+        let items = (self.fetch_range)(range);
+        assert_eq!(
+            items.len(),
+            expected,
+            "fetch_range returned the wrong number of elements"
+        );
+        items
+    }
+
+    /// Loads `range`, fetching the missing sub-ranges, and merges it with
+    /// the segments it overlaps or touches.
+    ///
+    /// The missing sub-ranges are all fetched before any segment is merged,
+    /// so a panicking `fetch_range` leaves the loaded segments untouched.
+    ///
+    /// # Returns
+    ///
+    /// The start index and a reference to the merged segment holding `range`.
+    fn load(&mut self, range: Range<usize>) -> (usize, &Vec<T>) {
+        let covering = self
+            .segments
+            .range(..=range.start)
+            .next_back()
+            .filter(|(start, segment)| *start + segment.len() >= range.end)
+            .map(|(start, _)| *start);
+        if let Some(start) = covering {
+            return (start, &self.segments[&start]);
+        }
+
+        let mut touching: Vec<(usize, usize)> = self
+            .segments
+            .range(..=range.end)
+            .rev()
+            .take_while(|(start, segment)| *start + segment.len() >= range.start)
+            .map(|(start, segment)| (*start, *start + segment.len()))
+            .collect();
+        touching.reverse();
+        let start = touching.first().map_or(range.start, |(segment_start, _)| {
+            range.start.min(*segment_start)
+        });
+
+        let mut position = start;
+        let mut gaps = Vec::with_capacity(touching.len());
+        for &(segment_start, segment_end) in &touching {
+            gaps.push(if position < segment_start {
+                self.fetch(position..segment_start)
+            } else {
+                Vec::new()
+            });
+            position = segment_end;
+        }
+        let tail = if position < range.end {
+            self.fetch(position..range.end)
+        } else {
+            Vec::new()
+        };
+
+        let mut merged = Vec::new();
+        for (gap, (segment_start, _)) in gaps.into_iter().zip(touching) {
+            append(&mut merged, gap);
+            append(&mut merged, self.segments.remove(&segment_start).unwrap());
+        }
+        append(&mut merged, tail);
+        (start, self.segments.entry(start).or_insert(merged))
+    }
+
+    /// Returns the elements in `range`, fetching only what is missing.
+    ///
+    /// It panics if `range` is decreasing or extends past the end of the vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The positions of the elements.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the elements.
+    pub fn slice(&mut self, range: Range<usize>) -> &[T] {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "range {:?} out of bounds for length {}",
+            range,
+            self.len
+        );
+        if range.is_empty() {
+            return &[];
+        }
+        let (start, segment) = self.load(range.clone());
+        &segment[range.start - start..range.end - start]
+    }
+
+    /// Returns the element at `index`, fetching it if it is missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the element.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the element, or `None` if `index` is out of bounds.
+    pub fn get(&mut self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        self.slice(index..index + 1).first()
+    }
+
+    /// Returns the length of the vector, without fetching anything.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the vector is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the ranges loaded so far, in order.
+    ///
+    /// Adjacent ranges are always merged, so consecutive ranges in the
+    /// result are separated by at least one missing index.
+    pub fn loaded_ranges(&self) -> Vec<Range<usize>> {
+        self.segments
+            .iter()
+            .map(|(start, segment)| *start..*start + segment.len())
+            .collect()
+    }
+
+    /// Checks if the element at `index` has been loaded.
+    pub fn is_loaded(&self, index: usize) -> bool {
+        self.segments
+            .range(..=index)
+            .next_back()
+            .is_some_and(|(start, segment)| index < start + segment.len())
+    }
+
+    /// Checks if nothing has been loaded yet.
+    ///
+    /// # Returns
+    ///
+    /// `true` if no range is loaded and `false` otherwise.
+    pub fn is_deferred(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+/// Appends `items` to `merged`, moving them instead of copying if `merged`
+/// is empty, so the first segment of a merge grows in place.
+fn append<T>(merged: &mut Vec<T>, mut items: Vec<T>) {
+    if merged.is_empty() {
+        *merged = items;
+    } else {
+        merged.append(&mut items);
+    }
+}
+
+/// Constructors for `Fetcher` sources.
+impl<T> RangedDeferredVec<T> {
+    /// Constructs a new instance of `RangedDeferredVec` loading through `Fetcher::fetch_range`.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The total number of elements.
+    /// * `fetcher` - The source of the elements.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `RangedDeferredVec` with nothing loaded.
+    pub fn from_fetcher<G>(
+        len: usize,
+        mut fetcher: G,
+    ) -> RangedDeferredVec<T, impl FnMut(Range<usize>) -> Vec<T>>
+    where
+        G: Fetcher<T>,
+    {
+        RangedDeferredVec::new(len, move |range| fetcher.fetch_range(range))
+    }
+}
+
+/// Unit tests for `RangedDeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Tests that only missing sub-ranges are fetched and loaded ranges are merged.
+    fn fetches_missing_ranges() {
+        let mut fetched = Vec::new();
+        let mut tst = RangedDeferredVec::new(1000, |range: Range<usize>| {
+            fetched.push(range.clone());
+            range.collect::<Vec<usize>>()
+        });
+        assert!(tst.is_deferred());
+        assert_eq!(tst.slice(100..110), &(100..110).collect::<Vec<_>>()[..]);
+        assert_eq!(tst.get(200), Some(&200));
+        assert_eq!(tst.slice(300..300), &[] as &[usize]);
+        assert_eq!(tst.loaded_ranges(), vec![100..110, 200..201]);
+        assert!(tst.is_loaded(105));
+        assert!(!tst.is_loaded(110));
+
+        assert_eq!(tst.slice(105..205).len(), 100);
+        assert_eq!(tst.loaded_ranges(), vec![100..205]);
+        assert_eq!(tst.slice(110..111), &[110]);
+        assert_eq!(tst.slice(205..206), &[205]);
+        assert_eq!(tst.loaded_ranges(), vec![100..206]);
+        assert_eq!(tst.get(1000), None);
+        drop(tst);
+        assert_eq!(
+            fetched,
+            vec![100..110, 200..201, 110..200, 201..205, 205..206]
+        );
+    }
+
+    #[test]
+    /// Tests that loaded ranges are served without fetching, and survive a
+    /// panicking `fetch_range`.
+    fn keeps_loaded_ranges() {
+        let mut fetched = Vec::new();
+        let mut tst = RangedDeferredVec::new(100, |range: Range<usize>| {
+            assert!(range.end <= 50, "unavailable");
+            fetched.push(range.clone());
+            range.collect::<Vec<usize>>()
+        });
+        tst.slice(0..10);
+        tst.slice(20..30);
+        assert_eq!(tst.get(5), Some(&5));
+        assert_eq!(tst.slice(22..28), &[22, 23, 24, 25, 26, 27]);
+
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tst.slice(5..60).len()));
+        assert!(result.is_err());
+        assert_eq!(tst.loaded_ranges(), vec![0..10, 20..30]);
+        assert_eq!(tst.slice(5..25).len(), 20);
+        assert_eq!(tst.loaded_ranges(), vec![0..30]);
+        drop(tst);
+        assert_eq!(fetched, vec![0..10, 20..30, 10..20, 10..20]);
+    }
+
+    #[test]
+    /// Tests loading ranges through a `Fetcher`.
+    fn loads_from_fetcher() {
+        let source = crate::fetcher::FromVec::new((0..50).collect::<Vec<u8>>());
+        let mut tst = RangedDeferredVec::from_fetcher(50, source);
+        assert_eq!(tst.slice(10..13), &[10, 11, 12]);
+        assert_eq!(tst.loaded_ranges(), vec![10..13]);
+    }
+}
@@ -0,0 +1,187 @@
+//! Serde integration, enabled by the `serde` feature.
+//!
+//! `SyncDeferredVec` and `CellDeferredVec` fetch the vector when serialized,
+//! and write its elements. `DeferredVec` cannot fetch through `&self`, so it
+//! writes the elements only when the vector is already fetched, and fails
+//! otherwise; the `loaded` module writes an empty sequence instead.
+//!
+//! Deserializing produces a pre-populated, non-deferred instance. The
+//! deserialized elements are its only source: a `DeferredVec` panics if it
+//! is fetched again, for example after `reset`, like one built with
+//! `From<Vec<T>>`.
+
+use crate::{CellDeferredVec, DeferredVec, Fetcher, SyncDeferredVec};
+use ::serde::ser::Error;
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serializes a fetched `DeferredVec`, and fails if it is deferred.
+impl<T, F> Serialize for DeferredVec<T, F>
+where
+    T: Serialize,
+    F: Fetcher<T>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.loaded() {
+            Some(vec) => vec.serialize(serializer),
+            None => Err(S::Error::custom(
+                "cannot serialize a deferred DeferredVec, call force() first",
+            )),
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for DeferredVec<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(DeferredVec::from(Vec::deserialize(deserializer)?))
+    }
+}
+
+/// Fetches the vector and serializes its elements.
+impl<T, F> Serialize for SyncDeferredVec<T, F>
+where
+    T: Serialize,
+    F: FnMut() -> Vec<T>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.force().serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for SyncDeferredVec<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(SyncDeferredVec::from(Vec::deserialize(deserializer)?))
+    }
+}
+
+/// Fetches the vector and serializes its elements.
+impl<T, F> Serialize for CellDeferredVec<T, F>
+where
+    T: Serialize,
+    F: FnMut() -> Vec<T>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.force().serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for CellDeferredVec<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(CellDeferredVec::from(Vec::deserialize(deserializer)?))
+    }
+}
+
+/// Serializes only already-fetched data, for use with `#[serde(with = "deferred_vector::serde::loaded")]`.
+///
+/// A deferred `DeferredVec` is written as an empty sequence instead of failing.
+pub mod loaded {
+    use crate::{DeferredVec, Fetcher};
+    use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serializes the fetched elements, or an empty sequence if deferred.
+    pub fn serialize<T, F, S>(
+        deferred_vec: &DeferredVec<T, F>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        F: Fetcher<T>,
+        S: Serializer,
+    {
+        deferred_vec.loaded().unwrap_or(&[]).serialize(serializer)
+    }
+
+    /// Deserializes a pre-populated `DeferredVec`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<DeferredVec<T>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        DeferredVec::deserialize(deserializer)
+    }
+}
+
+/// Unit tests for the serde integration.
+#[cfg(test)]
+mod tests {
+    use crate::{CellDeferredVec, DeferredVec, SyncDeferredVec};
+    use ::serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Report {
+        title: String,
+        rows: DeferredVec<u32>,
+        #[serde(with = "crate::serde::loaded")]
+        notes: DeferredVec<String>,
+        shared: SyncDeferredVec<u32>,
+        local: CellDeferredVec<u32>,
+    }
+
+    #[test]
+    /// Tests a JSON round trip of a struct holding deferred vectors.
+    fn json_round_trip() {
+        let mut report = Report {
+            title: String::from("q3"),
+            rows: DeferredVec::new(|| vec![1, 2]),
+            notes: DeferredVec::new(|| vec![String::from("unused")]),
+            shared: SyncDeferredVec::new(|| vec![3]),
+            local: CellDeferredVec::new(|| vec![4, 5]),
+        };
+        assert!(serde_json::to_string(&report).is_err());
+
+        report.rows.force();
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            json,
+            r#"{"title":"q3","rows":[1,2],"notes":[],"shared":[3],"local":[4,5]}"#
+        );
+        assert!(report.notes.is_deferred());
+
+        let mut restored: Report = serde_json::from_str(&json).unwrap();
+        assert!(!restored.rows.is_deferred());
+        assert!(!restored.shared.is_deferred());
+        assert!(!restored.local.is_deferred());
+        assert_eq!(restored.title, "q3");
+        assert_eq!(restored.rows.as_slice(), &[1, 2]);
+        assert_eq!(restored.notes.len(), 0);
+        assert_eq!(restored.shared.as_slice(), &[3]);
+        assert_eq!(restored.local.as_slice(), &[4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "built from a vector and cannot be fetched again")]
+    /// Tests that a deserialized `DeferredVec` panics when fetched again
+    /// after `reset`, instead of silently turning empty.
+    fn reset_after_deserialize() {
+        let mut rows: DeferredVec<u32> = serde_json::from_str("[1,2]").unwrap();
+        assert_eq!(rows.as_slice(), &[1, 2]);
+        rows.reset();
+        rows.as_slice();
+    }
+}
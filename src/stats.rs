@@ -0,0 +1,39 @@
+//! Fetch instrumentation.
+//!
+//! Every `DeferredVec` keeps `FetchStats`, readable through
+//! `DeferredVec::stats` without forcing a fetch.
+
+use std::time::{Duration, SystemTime};
+
+/// Counters and timings of the fetches of a `DeferredVec`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FetchStats {
+    /// The number of times the `fetch_function` was called.
+    pub fetch_count: u64,
+    /// The time spent in the `fetch_function`, over all fetches.
+    pub total_fetch_duration: Duration,
+    /// The time spent in the last call of the `fetch_function`.
+    pub last_fetch_duration: Option<Duration>,
+    /// The wall-clock time at which the last fetch completed.
+    pub last_loaded_at: Option<SystemTime>,
+    /// The number of elements produced by the last fetch.
+    pub last_fetch_len: usize,
+    /// The number of accesses served from the already fetched vector.
+    pub cache_hits: u64,
+}
+
+impl FetchStats {
+    /// Records a completed fetch.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - The time spent in the `fetch_function`.
+    /// * `len` - The number of elements fetched.
+    pub(crate) fn record_fetch(&mut self, duration: Duration, len: usize) {
+        self.fetch_count += 1;
+        self.total_fetch_duration += duration;
+        self.last_fetch_duration = Some(duration);
+        self.last_loaded_at = Some(SystemTime::now());
+        self.last_fetch_len = len;
+    }
+}
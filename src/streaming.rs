@@ -0,0 +1,295 @@
+//! Streaming deferred vectors.
+//!
+//! `StreamingDeferredVec` runs its producer on a background thread, started
+//! by the first access. The producer pushes elements into a `Sink` as they
+//! arrive, and `get(i)` only waits until element `i` has been produced, so
+//! consumption starts before the whole vector exists. The sink is a bounded
+//! buffer: once `capacity` elements are waiting to be received, `push`
+//! blocks until the consumer catches up.
+
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// The progress of a `StreamingDeferredVec`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamState {
+    /// The producer has not been started.
+    Deferred,
+    /// The given number of elements has been received, more may follow.
+    Partial(usize),
+    /// The producer has finished and every element has been received.
+    Complete,
+}
+
+/// The sending end of a `StreamingDeferredVec`, handed to its producer.
+pub struct Sink<T> {
+    sender: SyncSender<T>,
+}
+
+impl<T> Sink<T> {
+    /// Pushes the next element, blocking while the buffer is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The element to append to the vector.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the `StreamingDeferredVec` was dropped, in which case the
+    /// producer should stop.
+    pub fn push(&self, item: T) -> bool {
+        self.sender.send(item).is_ok()
+    }
+}
+
+/// The producer of a `StreamingDeferredVec`, before and after it is started.
+enum Producer<T, P> {
+    Deferred(P, usize),
+    Running(Receiver<T>, JoinHandle<()>),
+    Finished,
+}
+
+/// A lazily initialized vector whose elements become available as they
+/// are produced.
+///
+/// This struct holds the elements received so far and the `producer`,
+/// which runs on its own thread once started.
+pub struct StreamingDeferredVec<T, P = fn(Sink<T>)> {
+    vec: Vec<T>,
+    producer: Producer<T, P>,
+}
+
+/// Implement methods for `StreamingDeferredVec`.
+///
+/// Elements cross from the producer thread, hence the `Send` bounds.
+impl<T, P> StreamingDeferredVec<T, P>
+where
+    T: Send + 'static,
+    P: FnOnce(Sink<T>) + Send + 'static,
+{
+    /// Constructs a new instance of `StreamingDeferredVec`.
+    ///
+    /// It panics if `capacity` is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of produced elements that may wait to be received.
+    /// * `producer` - A function pushing the elements into the `Sink`, then returning.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `StreamingDeferredVec` whose producer is not started.
+    pub fn new(capacity: usize, producer: P) -> StreamingDeferredVec<T, P> {
+        assert!(capacity > 0, "capacity must be greater than zero");
+        StreamingDeferredVec {
+            vec: Vec::new(),
+            producer: Producer::Deferred(producer, capacity),
+        }
+    }
+
+    /// Starts the producer if it is deferred.
+    fn start(&mut self) {
+        if let Producer::Deferred(..) = self.producer {
+            if let Producer::Deferred(producer, capacity) =
+                std::mem::replace(&mut self.producer, Producer::Finished)
+            {
+                let (sender, receiver) = mpsc::sync_channel(capacity);
+                let handle = thread::spawn(move || producer(Sink { sender }));
+                self.producer = Producer::Running(receiver, handle);
+            }
+        }
+    }
+
+    /// Receives elements until `len` of them are available or the producer
+    /// has finished.
+    ///
+    /// A panic of the producer is propagated to the caller.
+    fn receive_until(&mut self, len: usize) {
+        self.start();
+        while self.vec.len() < len {
+            let Producer::Running(receiver, _) = &self.producer else {
+                return;
+            };
+            match receiver.recv() {
+                Ok(item) => self.vec.push(item),
+                Err(_) => {
+                    if let Producer::Running(_, handle) =
+                        std::mem::replace(&mut self.producer, Producer::Finished)
+                    {
+                        if let Err(payload) = handle.join() {
+                            std::panic::resume_unwind(payload);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the element at `index`, waiting only until it is produced.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the element.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the element, or `None` if the producer finished
+    /// before producing it.
+    pub fn get(&mut self, index: usize) -> Option<&T> {
+        self.receive_until(index.saturating_add(1));
+        self.vec.get(index)
+    }
+
+    /// Waits for the producer to finish and borrows all the elements.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the elements.
+    pub fn as_slice(&mut self) -> &[T] {
+        self.receive_until(usize::MAX);
+        &self.vec
+    }
+
+    /// Waits for the producer to finish and returns the number of elements.
+    ///
+    /// # Returns
+    ///
+    /// The length of the vector.
+    pub fn len(&mut self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Waits for the producer to finish and checks if there are no elements.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the producer produced no element.
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Methods that never start the producer nor wait for it.
+impl<T, P> StreamingDeferredVec<T, P> {
+    /// Borrows the elements received so far.
+    pub fn loaded(&self) -> &[T] {
+        &self.vec
+    }
+
+    /// Returns the progress of the vector.
+    ///
+    /// Elements still waiting in the buffer are not counted until an
+    /// access receives them.
+    pub fn state(&self) -> StreamState {
+        match self.producer {
+            Producer::Deferred(..) => StreamState::Deferred,
+            Producer::Running(..) => StreamState::Partial(self.vec.len()),
+            Producer::Finished => StreamState::Complete,
+        }
+    }
+
+    /// Checks if the producer has not been started.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the state is `StreamState::Deferred`.
+    pub fn is_deferred(&self) -> bool {
+        self.state() == StreamState::Deferred
+    }
+}
+
+/// Constructors for iterator sources.
+impl<T> StreamingDeferredVec<T>
+where
+    T: Send + 'static,
+{
+    /// Constructs a new instance of `StreamingDeferredVec` producing the
+    /// items of `iter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of produced elements that may wait to be received.
+    /// * `iter` - The items, pulled on the producer thread.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `StreamingDeferredVec` whose producer is not started.
+    pub fn from_iter<I>(
+        capacity: usize,
+        iter: I,
+    ) -> StreamingDeferredVec<T, impl FnOnce(Sink<T>) + Send + 'static>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: Send + 'static,
+    {
+        let iter = iter.into_iter();
+        StreamingDeferredVec::new(capacity, move |sink: Sink<T>| {
+            for item in iter {
+                if !sink.push(item) {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+/// Unit tests for `StreamingDeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    /// Tests that `get` returns as soon as the element is produced.
+    fn get_waits_for_one_element() {
+        let (gate, wait) = mpsc::channel();
+        let mut tst = StreamingDeferredVec::new(4, move |sink: Sink<u32>| {
+            sink.push(1);
+            wait.recv().unwrap();
+            sink.push(2);
+        });
+        assert_eq!(tst.state(), StreamState::Deferred);
+
+        assert_eq!(tst.get(0), Some(&1));
+        assert_eq!(tst.state(), StreamState::Partial(1));
+        assert_eq!(tst.loaded(), &[1]);
+
+        gate.send(()).unwrap();
+        assert_eq!(tst.get(1), Some(&2));
+        assert_eq!(tst.get(2), None);
+        assert_eq!(tst.state(), StreamState::Complete);
+        assert_eq!(tst.len(), 2);
+    }
+
+    #[test]
+    /// Tests that a full buffer blocks the producer.
+    fn bounded_buffer_applies_backpressure() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&produced);
+        let mut tst = StreamingDeferredVec::from_iter(
+            2,
+            (0..100).inspect(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+        assert_eq!(tst.get(0), Some(&0));
+        std::thread::sleep(Duration::from_millis(50));
+        // One element received, two buffered and one blocked in `push`.
+        assert!(produced.load(Ordering::SeqCst) <= 4);
+        assert_eq!(tst.as_slice(), (0..100).collect::<Vec<_>>());
+        assert_eq!(tst.state(), StreamState::Complete);
+    }
+
+    #[test]
+    #[should_panic(expected = "producer failed")]
+    /// Tests that a panic of the producer reaches the consumer.
+    fn propagates_producer_panics() {
+        let mut tst = StreamingDeferredVec::new(1, |sink: Sink<u32>| {
+            sink.push(1);
+            panic!("producer failed");
+        });
+        tst.as_slice();
+    }
+}
@@ -0,0 +1,190 @@
+//! Thread-safe deferred vectors.
+//!
+//! `SyncDeferredVec` can be shared between threads (for example behind an
+//! `Arc`) and accessed through `&self`. The `fetch_function` runs exactly
+//! once; concurrent callers block until that fetch finishes.
+
+use std::ops::Deref;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// A thread-safe, lazily initialized vector.
+///
+/// This struct holds a `OnceLock<Vec<T>>` to store the vector and a
+/// `Mutex` guarding the `fetch_function`, so that `SyncDeferredVec` is
+/// `Sync` whenever `T` is `Send + Sync` and `F` is `Send`.
+pub struct SyncDeferredVec<T, F = fn() -> Vec<T>> {
+    vec: OnceLock<Vec<T>>,
+    fetch_function: Mutex<F>,
+}
+
+/// Implement methods for `SyncDeferredVec`.
+impl<T, F> SyncDeferredVec<T, F>
+where
+    F: FnMut() -> Vec<T>,
+{
+    /// Constructs a new instance of `SyncDeferredVec`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fetch_function` - A function or closure to initialize the vector.
+    ///
+    /// # Returns
+    ///
+    /// A new, deferred instance of `SyncDeferredVec`.
+    pub fn new(fetch_function: F) -> SyncDeferredVec<T, F> {
+        SyncDeferredVec {
+            vec: OnceLock::new(),
+            fetch_function: Mutex::new(fetch_function),
+        }
+    }
+
+    /// Fetches and initializes the vector if it has not been fetched yet.
+    ///
+    /// Only one thread runs the `fetch_function`; the others block until it
+    /// finishes. If the `fetch_function` panics, the vector stays deferred
+    /// and the next access fetches again.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the fetched vector.
+    fn fetch(&self) -> &Vec<T> {
+        self.vec.get_or_init(|| {
+            let mut fetch_function = self
+                .fetch_function
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            (fetch_function)()
+        })
+    }
+
+    /// Fetches the vector and returns a reference to it.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the fetched vector.
+    pub fn force(&self) -> &Vec<T> {
+        self.fetch()
+    }
+
+    /// Fetches the vector and borrows its elements as a slice.
+    ///
+    /// # Returns
+    ///
+    /// A slice over the fetched elements.
+    pub fn as_slice(&self) -> &[T] {
+        self.fetch()
+    }
+
+    /// Returns the length of the fetched vector.
+    ///
+    /// # Returns
+    ///
+    /// The length of the fetched vector.
+    pub fn len(&self) -> usize {
+        self.fetch().len()
+    }
+
+    /// Checks if the fetched vector is empty.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the fetched vector has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks if the vector is initialized.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the vector has not been fetched yet and `false` otherwise.
+    pub fn is_deferred(&self) -> bool {
+        self.vec.get().is_none()
+    }
+}
+
+/// Methods returning owned copies of the vector.
+impl<T, F> SyncDeferredVec<T, F>
+where
+    T: std::clone::Clone,
+    F: FnMut() -> Vec<T>,
+{
+    /// Fetches and returns a copy of the vector.
+    ///
+    /// # Returns
+    ///
+    /// The fetched vector.
+    pub fn get(&self) -> Vec<T> {
+        self.fetch().clone()
+    }
+}
+
+/// Dereferencing fetches the vector, like `std::sync::LazyLock`.
+impl<T, F> Deref for SyncDeferredVec<T, F>
+where
+    F: FnMut() -> Vec<T>,
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.fetch()
+    }
+}
+
+/// Builds a fetched `SyncDeferredVec` from a vector.
+///
+/// Its `fetch_function` returns an empty vector and is never called.
+impl<T> From<Vec<T>> for SyncDeferredVec<T> {
+    fn from(vec: Vec<T>) -> SyncDeferredVec<T> {
+        let deferred_vec = SyncDeferredVec::new(Vec::new as fn() -> Vec<T>);
+        let _ = deferred_vec.vec.set(vec);
+        deferred_vec
+    }
+}
+
+/// Unit tests for `SyncDeferredVec`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    /// Tests that many threads racing on a deferred vector run the fetch exactly once.
+    fn fetches_once_across_threads() {
+        const THREADS: usize = 32;
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&fetches);
+        let tst = Arc::new(SyncDeferredVec::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            (0..1000).collect::<Vec<u32>>()
+        }));
+        assert!(tst.is_deferred());
+
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|i| {
+                let tst = Arc::clone(&tst);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    if i % 2 == 0 {
+                        tst.len()
+                    } else {
+                        tst.as_slice().len()
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 1000);
+        }
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(!tst.is_deferred());
+        assert_eq!(tst[999], 999);
+    }
+}
@@ -0,0 +1,174 @@
+//! Diagnostics through `tracing`, enabled by the `tracing` feature.
+//!
+//! Each fetch of a `DeferredVec` runs inside a `deferred_vec.fetch` span
+//! recording the vector's name, the element type, the element count and the
+//! fetch duration. Cache hits, resets and failed fetches are emitted as
+//! events. Without the feature, these functions compile to nothing.
+
+use std::time::Duration;
+
+/// The span of a fetch in progress.
+///
+/// Dropping it while the `fetch_function` is unwinding emits an error event.
+pub(crate) struct FetchSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+}
+
+impl FetchSpan {
+    /// Opens and enters the span of a fetch.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the deferred vector.
+    /// * `element_type` - The type name of the elements.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn enter(name: &str, element_type: &str) -> FetchSpan {
+        FetchSpan {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "deferred_vec.fetch",
+                name,
+                element_type,
+                len = tracing::field::Empty,
+                duration = tracing::field::Empty,
+            )
+            .entered(),
+        }
+    }
+
+    /// Records the outcome of a successful fetch on the span.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The number of elements fetched.
+    /// * `duration` - The time spent in the `fetch_function`.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn record(&self, len: usize, duration: Duration) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("len", len);
+            self.span
+                .record("duration", tracing::field::debug(duration));
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for FetchSpan {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            tracing::error!("deferred vector fetch panicked");
+        }
+    }
+}
+
+/// Emits the event of an access served from the fetched vector.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn cache_hit(name: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(name, "deferred vector cache hit");
+}
+
+/// Emits the event of fetched data being dropped.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn reset(name: &str, len: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(name, len, "deferred vector reset");
+}
+
+/// Emits the event of a fetch that returned an error.
+///
+/// # Arguments
+///
+/// * `name` - The name of the deferred vector.
+/// * `element_type` - The type name of the elements.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn failure(name: &str, element_type: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(name, element_type, "deferred vector fetch failed");
+}
+
+/// Unit tests for the `tracing` integration.
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::{DeferredVec, TryDeferredVec};
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Formats the fields of spans and events as `name=value` pairs.
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    /// A subscriber writing every span and event to a shared log.
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields(format!("span {}", span.metadata().name()));
+            span.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            let mut fields = Fields(String::from("record"));
+            values.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(format!("{}", event.metadata().level()));
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    /// Tests the fetch span and the cache hit, reset and failure events.
+    fn traces_fetches() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Recorder(Arc::clone(&log)), || {
+            let mut tst = DeferredVec::new(|| vec![1u8, 2]).with_name("rows");
+            tst.as_slice();
+            tst.as_slice();
+            tst.reset();
+            let mut failing = TryDeferredVec::new(|| Err::<Vec<u8>, _>("offline"));
+            assert!(failing.try_len().is_err());
+        });
+        let log = log.lock().unwrap();
+        assert_eq!(
+            log[0],
+            "span deferred_vec.fetch name=\"rows\" element_type=\"u8\""
+        );
+        assert_eq!(log[1], "record len=2");
+        assert!(log[2].starts_with("record duration="));
+        assert_eq!(
+            log[3],
+            "TRACE message=deferred vector cache hit name=\"rows\""
+        );
+        assert_eq!(
+            log[4],
+            "DEBUG message=deferred vector reset name=\"rows\" len=2"
+        );
+        assert!(log[5].starts_with("WARN message=deferred vector fetch failed"));
+    }
+}